@@ -1,35 +1,103 @@
 use actix_web::{web, App, HttpResponse, HttpServer};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::EnvFilter;
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Game {
     id: String,
+    #[serde(default)]
+    ruleset: Ruleset,
+    // Milliseconds the engine waits for our answer
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    // Where the game is played from, e.g. "tournament", "league", "ladder" or "custom"
+    #[serde(default)]
+    source: String,
 }
 
-#[derive(Deserialize)]
+fn default_timeout() -> u64 {
+    500
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct Ruleset {
+    // e.g. "standard", "royale", "constrictor"
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    settings: RulesetSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct RulesetSettings {
+    #[serde(default)]
+    hazard_damage_per_turn: i32,
+    // Percent chance of a food spawn each turn
+    #[serde(default)]
+    food_spawn_chance: i32,
+    // Below this much food on the board the engine spawns more every turn
+    #[serde(default)]
+    minimum_food: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct Board {
     height: i32,
     width: i32,
+    #[serde(deserialize_with = "unique_coords")]
     food: Vec<Coord>,
+    #[serde(default)]
+    hazards: Vec<Coord>,
     snakes: Vec<Snake>,
 }
 
-#[derive(Deserialize, Clone)]  // Add Clone here
+// Drops repeated coordinates, keeping the first, so a malformed payload listing
+// the same food twice doesn't count it twice
+fn unique_coords<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<Coord>, D::Error> {
+    let mut coords = Vec::<Coord>::deserialize(deserializer)?;
+    let mut seen = HashSet::new();
+    coords.retain(|coord| seen.insert(coord.clone()));
+    Ok(coords)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 struct Coord {
     x: i32,
     y: i32,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Snake {
     id: String,
     body: Vec<Coord>,
     health: i32,
+    // Appearance chosen by the snake's author, kept so replays stay complete
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    customizations: Option<Customizations>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct Customizations {
+    color: String,
+    head: String,
+    tail: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 struct GameState {
     game: Game,
+    // Older payloads may leave it out: treated as the first turn
+    #[serde(default)]
     turn: i32,
     board: Board,
     you: Snake,
@@ -37,7 +105,33 @@ struct GameState {
 
 #[derive(Serialize)]
 struct MoveResponse {
-    r#move: String,
+    r#move: Direction,
+    // Extra detail for debugging viewers; the engine ignores unknown fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    debug: Option<DebugInfo>,
+}
+
+#[derive(Serialize)]
+struct DebugInfo {
+    reason: String,
+    // Final score per direction, null for moves ruled out as unsafe
+    scores: BTreeMap<String, Option<f64>>,
+    // Wall-clock time spent deciding
+    latency_ms: f64,
+}
+
+impl DebugInfo {
+    fn from_decision(decision: &Decision, latency: Duration) -> Self {
+        DebugInfo {
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            reason: decision.reason.to_string(),
+            scores: decision
+                .scores
+                .iter()
+                .map(|m| (m.direction.to_string(), m.score.is_finite().then_some(m.score)))
+                .collect(),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -45,338 +139,6050 @@ struct StartResponse {
     color: String,
 }
 
-// Move this near other struct definitions
-#[derive(Clone, Debug)]
-struct Move {
-    direction: String,
-    score: f64,
+// Tunable settings, read once at startup from an optional `snake.toml`
+// (or the file named by CONFIG_PATH) with environment variables as overrides
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+struct Config {
+    appearance: Appearance,
+    // Address the server binds to (HOST, PORT)
+    host: String,
+    port: u16,
+    weights: Weights,
+    // Upper bound on cells visited by a single flood fill (FLOOD_FILL_MAX_CELLS).
+    // Defaults to the board area, i.e. uncapped.
+    flood_fill_max_cells: Option<usize>,
+    // In a 1v1 where both snakes are above this health (DUEL_FOOD_MIN_HEALTH),
+    // food is only pursued if it keeps our space differential non-negative
+    duel_food_min_health: Option<i32>,
+    // In a 1v1, rank food by the Voronoi control it would give us as well as by
+    // distance (DUEL_FOOD_CONTROL)
+    duel_food_control: bool,
+    // Forgo food while fed if heading for it brings us closer than this to a
+    // longer opponent's head (FOOD_SAFE_DISTANCE)
+    food_safe_distance: Option<i32>,
+    // Scale the head-to-head penalty by how easily we could escape the cell
+    // (ESCAPE_SCALED_THREAT)
+    escape_scaled_threat: bool,
+    // Predict each opponent's next move and log how often we were right
+    // (TRACK_PREDICTIONS)
+    track_predictions: bool,
+    // Once no opponent can reach our space, just pick the roomiest move
+    // (SEPARATED_ENDGAME)
+    separated_endgame: bool,
+    // In a 1v1, head for the center for this many turns from the start
+    // (DUEL_OPENING_TURNS); off when unset
+    duel_opening_turns: Option<i32>,
+    // Moves leaving fewer reachable cells than this are ruled out while any move
+    // keeps more (SPACE_FLOOR); off when unset
+    space_floor: Option<i32>,
+    // When health runs low, follow a route collecting several nearby food
+    // instead of re-targeting the nearest each turn (PLAN_FOOD_ROUTE)
+    plan_food_route: bool,
+    // Drop opponents reported with zero health before deciding, as the engine
+    // removes them this turn (IGNORE_DEAD_SNAKES)
+    ignore_dead_snakes: bool,
+    // In solo and constrictor games, follow a cycle through every cell while its
+    // next step is safe (FILL_CYCLE)
+    fill_cycle: bool,
+    // Turns of circling over our own recent path without growing before the
+    // food and space weights are scaled by STALEMATE_SHIFT (STALEMATE_TURNS);
+    // off when unset
+    stalemate_turns: Option<u32>,
+    stalemate_shift: f64,
+    // Measure distances around the edges in "wrapped" games (WRAPPED_DISTANCE)
+    wrapped_distance: bool,
+    // Set per game by `for_ruleset`: the board wraps and distances should too
+    #[serde(skip)]
+    toroidal: bool,
+    // Enables the /debug routes (DEBUG)
+    debug: bool,
+    // Directory /debug/replay may read recordings from (REPLAY_DIR); replays are
+    // refused without it
+    replay_dir: Option<String>,
+    // Number of our own moves the duel search looks ahead on a typical board
+    // (SEARCH_DEPTH). Sparse boards start one deeper, crowded ones one shallower,
+    // and iterative deepening continues while the time budget allows.
+    search_depth: u32,
+    // Deepest the iterative deepening may go (MAX_SEARCH_DEPTH)
+    max_search_depth: u32,
+    // Milliseconds kept free of the engine timeout for network latency (LATENCY_MARGIN_MS)
+    latency_margin_ms: u64,
+    // Search budget in milliseconds never exceeded whatever the game's timeout
+    // says (HARD_MOVE_CAP_MS)
+    hard_move_cap_ms: u64,
+    // File that turns ending in a fallback move are appended to, in replay format
+    // (FALLBACK_LOG). Without it their payload goes to the error log instead.
+    fallback_log: Option<String>,
+    // Seconds between self-check log lines (SELF_CHECK_INTERVAL); off when unset
+    self_check_interval: Option<u64>,
+    // Blend between the one-move static score and the search value (SEARCH_BLEND):
+    // score = alpha * static + (1 - alpha) * minimax. 0 is pure minimax, 1 pure greedy.
+    search_blend: f64,
+    // How many of the search's plies model the opponent adversarially (OPPONENT_DEPTH).
+    // Deeper plies assume the opponent simply takes its roomiest safe move, which
+    // keeps the branching factor at 4 instead of 16. Defaults to SEARCH_DEPTH, so
+    // only the extra plies added by iterative deepening use the greedy model.
+    opponent_depth: Option<u32>,
+    // How many of the nearest opponents the search moves (SEARCH_OPPONENTS); the
+    // rest stay where they are. Each one multiplies the branching factor by 4.
+    search_opponents: usize,
+    // Extra health kept in reserve over the cost of reaching the nearest food;
+    // below it food becomes urgent (STARVATION_MARGIN)
+    starvation_margin: i32,
+    // Pessimistic move resolution (ASSUME_WORST_RESOLUTION): cells an opponent
+    // could also enter and tails that might not vacate count as unsafe whenever
+    // some other move avoids them
+    assume_worst_resolution: bool,
+    // Opponent tails at full health count as unsafe whenever some other move
+    // avoids them (CONSERVATIVE_TAILS)
+    conservative_tails: bool,
+    // Reachable cells a move must leave us (MIN_SPACE); defaults to our body length
+    min_space: Option<usize>,
+    // Assume snakes next to food eat it (MODEL_OPPONENT_GROWTH): their tails stay
+    // put in space estimates. An approximation: they may not actually eat.
+    model_opponent_growth: bool,
+    // Count a snake next to food one longer when judging whether we win a
+    // head-to-head with it (HEAD_TO_HEAD_GROWTH), so we don't start one it wins
+    // by eating first
+    head_to_head_growth: bool,
+    // Where (0, 0) is (COORD_ORIGIN). The official API uses bottom-left with y
+    // increasing upward; some older tools and test harnesses use top-left.
+    coord_origin: CoordOrigin,
+    // Overall play style (PERSONALITY)
+    personality: Personality,
+    // Turn from which an aggressive personality starts hunting; before it we play
+    // balanced to get through the opening scramble (AGGRESSION_START_TURN)
+    aggression_start_turn: i32,
+    // How many segments longer we must be to count a head-to-head as ours
+    // (HEAD_TO_HEAD_MARGIN). 1 means strictly longer; higher values allow for
+    // the opponent eating on the way in.
+    head_to_head_margin: usize,
+    // Health below which we care about keeping some food reachable (FOOD_ACCESS_HEALTH)
+    food_access_health: i32,
+    // How the last-resort move is picked when deciding fails (FALLBACK_STRATEGY)
+    fallback_strategy: FallbackStrategy,
+    // What a reachable hazard cell is worth in the space terms, relative to an open
+    // cell (HAZARD_SPACE_WEIGHT). Hazards can be crossed but drain health.
+    hazard_space_weight: f64,
+    // Fraction of the hazard cell penalty applied to cells next to a hazard, a
+    // margin against being forced in later (HAZARD_BUFFER)
+    hazard_buffer: f64,
+    // Strategy presets keyed by `game.source`, e.g. a cautious one for "tournament".
+    // Only settable from the config file.
+    source_presets: HashMap<String, Preset>,
+    // Runs `decide` on the blocking thread pool instead of the async worker
+    // (OFFLOAD_COMPUTE), keeping workers responsive with many games in flight
+    offload_compute: bool,
+    // Manhattan distance within which an opponent head makes an edge cell crowded (CROWDING_RADIUS)
+    crowding_radius: i32,
+    // Experimental (MIRROR_OPPONENT): in a 1v1 where we hold more space, shadow the
+    // opponent's moves to keep the current standoff instead of closing in
+    mirror_opponent: bool,
+    // Skips every scoring term and the search for the roomiest safe move
+    // (PREDICTABLE): a deterministic baseline that is hard to exploit
+    predictable: bool,
+    // Only plays moves that keep our tail reachable, by space (SAFE_TAIL)
+    safe_tail: bool,
+    // Fraction of our reachable space lost in one turn that counts as being boxed in (BOX_IN_SHRINK)
+    box_in_shrink: f64,
+    // Picks the move with the largest worst-case space one ply ahead instead of
+    // the usual scoring (MAXIMIN)
+    maximin: bool,
+    // Matches our id against the board ignoring case (CASE_INSENSITIVE_IDS); ids
+    // are always trimmed
+    case_insensitive_ids: bool,
+    // Fraction of turns, 0 to 1, that log the per-move score breakdown
+    // (LOG_SAMPLE_RATE); warnings, errors and fallbacks always log
+    log_sample_rate: f64,
+    // Drift toward the center while food is below `minimumFood` (ANTICIPATE_FOOD_SPAWNS)
+    anticipate_food_spawns: bool,
+    // Opponents at or below this health are worth starving out (FOOD_DENIAL_HEALTH)
+    food_denial_health: i32,
+    // How many of the nearest opponents the threat terms and the search look at
+    // (MAX_MODELED_OPPONENTS); the rest only block cells. Unset models them all.
+    max_modeled_opponents: Option<usize>,
 }
 
-impl Move {
-    fn new(direction: &str) -> Self {
-        Move {
-            direction: direction.to_string(),
-            score: 0.0,
+// Overrides applied to games from a given source; unset fields keep the base config.
+// A `weights` table replaces all weights, with unlisted ones at their defaults.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct Preset {
+    personality: Option<Personality>,
+    weights: Option<Weights>,
+}
+
+// How the snake looks in the game viewer (SNAKE_AUTHOR, SNAKE_COLOR, SNAKE_HEAD, SNAKE_TAIL)
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+struct Appearance {
+    author: String,
+    color: String,
+    head: String,
+    tail: String,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Appearance {
+            author: "Dumas".to_string(),
+            color: "#FF0000".to_string(),
+            head: "default".to_string(),
+            tail: "default".to_string(),
         }
     }
 }
 
-// Add this helper function early in the file
-fn get_new_position(head: &Coord, direction: &str) -> Coord {
-    match direction {
-        "up" => Coord { x: head.x, y: head.y + 1 },
-        "down" => Coord { x: head.x, y: head.y - 1 },
-        "left" => Coord { x: head.x - 1, y: head.y },
-        "right" => Coord { x: head.x + 1, y: head.y },
-        _ => Coord { x: head.x, y: head.y },
+// Multipliers for the terms of the static move evaluation (WEIGHT_SPACE, WEIGHT_FOOD, ...).
+// With DEBUG on they can be swapped at runtime through /debug/weights.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+struct Weights {
+    space: f64,
+    food: f64,
+    threat: f64,
+    // Repulsion from the heads of longer-or-equal opponents
+    avoid_larger: f64,
+    center: f64,
+    // How fast center attraction fades: the center weight is scaled by
+    // exp(-center_decay * turn), so early positioning gives way to space management
+    center_decay: f64,
+    // Penalty for stepping back onto a cell our head visited in the last few turns
+    loop_penalty: f64,
+    // Penalty for positions where our head can no longer reach our own tail
+    tail_access: f64,
+    // Penalty for moves whose reachable space is below the MIN_SPACE floor
+    min_space_penalty: f64,
+    // Bonus for the move opening into the largest region when our moves split the board
+    larger_region: f64,
+    // Reward per cell taken from a shorter, wall-hugging opponent's space (aggressive only)
+    cutoff: f64,
+    // Reward per exit taken from the cutoff target, counted twice for its way
+    // forward along the wall (aggressive only)
+    corner_trap: f64,
+    // Reward per cell taken from the most dangerous opponent's space (aggressive only)
+    pressure: f64,
+    // Penalty for cutting ourselves off from all food below FOOD_ACCESS_HEALTH;
+    // keeping some food reachable earns half of it back
+    food_access: f64,
+    // Bonus for taking a chokepoint that seals opponents out of the larger side,
+    // scaled by the size difference of the two sides as a fraction of the board
+    chokepoint: f64,
+    // Penalty per opponent head within CROWDING_RADIUS for moving onto an edge cell
+    edge_crowding: f64,
+    // Bonus for keeping our distance to the opponent in MIRROR_OPPONENT mode
+    mirror: f64,
+    // Reward per safe move we can expect to have over the next plies (see future_flexibility)
+    flexibility: f64,
+    // Reward per hungry opponent whose shortest path to food we step onto (aggressive only)
+    food_denial: f64,
+    // Bonus per food inside the region a move leads into, whether or not we head for it
+    region_food: f64,
+    // Bonus for roomy moves, as a fraction of the board, while our space is
+    // shrinking fast (see BOX_IN_SHRINK)
+    escape: f64,
+    // Penalty for the hazard density of the board quadrant a move ends in (royale only)
+    hazard_quadrant: f64,
+    // Penalty for moving onto a hazard cell (royale only); cells next to one take
+    // the HAZARD_BUFFER fraction of it
+    hazard_cell: f64,
+    // Extra center attraction while food is below the ruleset's minimum, where
+    // spawns are guaranteed and tend to land in open areas (ANTICIPATE_FOOD_SPAWNS)
+    spawn_center: f64,
+    // Reward for keeping the body stretched out rather than coiled (see body_spread);
+    // meant for large open boards, off by default
+    spine: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            space: 5.0,
+            food: 1.0,
+            threat: 1.0,
+            avoid_larger: 30.0,
+            center: 1.0,
+            center_decay: 0.01,
+            loop_penalty: 15.0,
+            tail_access: 50.0,
+            min_space_penalty: 1000.0,
+            larger_region: 100.0,
+            cutoff: 10.0,
+            pressure: 5.0,
+            corner_trap: 25.0,
+            food_access: 20.0,
+            chokepoint: 200.0,
+            edge_crowding: 10.0,
+            mirror: 25.0,
+            flexibility: 5.0,
+            food_denial: 15.0,
+            region_food: 3.0,
+            escape: 100.0,
+            hazard_quadrant: 30.0,
+            hazard_cell: 0.0,
+            spawn_center: 1.0,
+            spine: 0.0,
+        }
     }
 }
 
-fn manhattan_distance(a: &Coord, b: &Coord) -> i32 {
-    (a.x - b.x).abs() + (a.y - b.y).abs()
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Personality {
+    #[default]
+    Balanced,
+    // Uses its length advantage to hunt shorter opponents down over several turns
+    Aggressive,
+    // Grows as fast as it safely can: food dominates and threats barely register,
+    // leaving only the hard safety checks between it and the next meal
+    MaximizeLength,
 }
 
-fn is_safe_move(pos: &Coord, board: &Board, snake_length: usize) -> bool {
-    // Check board boundaries
-    if pos.x < 0 || pos.x >= board.width || pos.y < 0 || pos.y >= board.height {
-        return false;
+impl std::str::FromStr for Personality {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().replace(['_', '-'], "").as_str() {
+            "balanced" => Ok(Personality::Balanced),
+            "aggressive" => Ok(Personality::Aggressive),
+            "maximizelength" => Ok(Personality::MaximizeLength),
+            other => Err(format!("unknown personality: {other}")),
+        }
     }
+}
 
-    // Check snake collisions
-    for snake in &board.snakes {
-        for (i, segment) in snake.body.iter().enumerate() {
-            if pos.x == segment.x && pos.y == segment.y {
-                // Allow moving to tail position if it's going to move
-                if !(i == snake.body.len() - 1 && snake.body.len() == snake_length) {
-                    return false;
-                }
-            }
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum FallbackStrategy {
+    // The first safe move in up, down, left, right order
+    #[default]
+    FirstSafe,
+    // The safe move with the most reachable cells
+    LargestSpace,
+    // Keep heading the way we moved last turn if that is safe
+    Straight,
+    // The safe move ending closest to the center of the board
+    Center,
+}
+
+impl std::str::FromStr for FallbackStrategy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().replace(['_', '-'], "").as_str() {
+            "firstsafe" => Ok(FallbackStrategy::FirstSafe),
+            "largestspace" => Ok(FallbackStrategy::LargestSpace),
+            "straight" => Ok(FallbackStrategy::Straight),
+            "center" => Ok(FallbackStrategy::Center),
+            other => Err(format!("unknown fallback strategy: {other}")),
         }
     }
+}
 
-    true
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CoordOrigin {
+    #[default]
+    BottomLeft,
+    TopLeft,
 }
 
-// Update evaluate_food to be more efficient and actually use health parameter
-fn evaluate_food(pos: &Coord, board: &Board, health: i32) -> Option<(f64, String)> {
-    let mut nearest_food = None;
-    let mut min_dist = f64::MAX;
+impl std::str::FromStr for CoordOrigin {
+    type Err = String;
 
-    for food in &board.food {
-        let dist = manhattan_distance(pos, food) as f64;
-        if dist < min_dist {
-            min_dist = dist;
-            let dir = if (food.x - pos.x).abs() > (food.y - pos.y).abs() {
-                if food.x > pos.x { "right" } else { "left" }
-            } else {
-                if food.y > pos.y { "up" } else { "down" }
-            }.to_string();
-            nearest_food = Some((dist, dir));
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().replace(['_', '-'], "").as_str() {
+            "bottomleft" => Ok(CoordOrigin::BottomLeft),
+            "topleft" => Ok(CoordOrigin::TopLeft),
+            other => Err(format!("unknown coordinate origin: {other}")),
         }
     }
+}
 
-    // Adjust score based on health
-    nearest_food.map(|(dist, dir)| {
-        let urgency = if health < 25 { 1.5 } else { 1.0 };
-        (dist * urgency, dir)
-    })
+impl CoordOrigin {
+    // The engine's name for a move worked out the way every position helper
+    // does, with y increasing upward: from a top-left origin that is "down"
+    fn engine_direction(self, direction: Direction) -> Direction {
+        match (self, direction) {
+            (CoordOrigin::TopLeft, Direction::Up | Direction::Down) => direction.opposite(),
+            _ => direction,
+        }
+    }
 }
 
-fn calculate_food_score(distance: f64, health: i32) -> f64 {
-    let base_score = 100.0 - distance;
-    
-    // Increase urgency when health is low
-    if health < 25 {
-        base_score * 3.0
-    } else if health < 50 {
-        base_score * 1.5
-    } else {
-        base_score
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            appearance: Appearance::default(),
+            host: "0.0.0.0".to_string(),
+            port: 8000,
+            weights: Weights::default(),
+            flood_fill_max_cells: None,
+            duel_food_min_health: None,
+            duel_food_control: false,
+            food_safe_distance: None,
+            escape_scaled_threat: false,
+            track_predictions: false,
+            separated_endgame: false,
+            duel_opening_turns: None,
+            space_floor: None,
+            plan_food_route: false,
+            ignore_dead_snakes: true,
+            fill_cycle: false,
+            stalemate_turns: None,
+            stalemate_shift: 2.0,
+            wrapped_distance: true,
+            toroidal: false,
+            debug: false,
+            replay_dir: None,
+            search_depth: 2,
+            max_search_depth: 4,
+            latency_margin_ms: 150,
+            hard_move_cap_ms: 450,
+            fallback_log: None,
+            self_check_interval: None,
+            search_blend: 0.0,
+            opponent_depth: None,
+            search_opponents: 1,
+            starvation_margin: 5,
+            assume_worst_resolution: false,
+            conservative_tails: false,
+            min_space: None,
+            model_opponent_growth: false,
+            head_to_head_growth: true,
+            coord_origin: CoordOrigin::default(),
+            personality: Personality::default(),
+            aggression_start_turn: 0,
+            head_to_head_margin: 1,
+            food_access_health: 75,
+            fallback_strategy: FallbackStrategy::default(),
+            hazard_space_weight: 0.3,
+            hazard_buffer: 0.5,
+            source_presets: HashMap::new(),
+            offload_compute: false,
+            crowding_radius: 3,
+            mirror_opponent: false,
+            predictable: false,
+            safe_tail: false,
+            box_in_shrink: 0.25,
+            maximin: false,
+            case_insensitive_ids: false,
+            log_sample_rate: 1.0,
+            anticipate_food_spawns: false,
+            food_denial_health: 40,
+            max_modeled_opponents: None,
+        }
     }
 }
 
-fn evaluate_threats(pos: &Coord, board: &Board, you: &Snake) -> f64 {
-    let mut threat_score = 0.0;
+impl Config {
+    // Reads the config file if there is one, then applies environment overrides.
+    // With STRICT_CONFIG a malformed file or override is an error; otherwise it is
+    // reported and replaced by the default.
+    fn load() -> Result<Self, String> {
+        let path = process_env("CONFIG_PATH").unwrap_or_else(|| "snake.toml".to_string());
+        let contents = std::fs::read_to_string(&path).ok();
+        Config::load_from(&path, contents.as_deref(), &process_env)
+    }
 
-    for snake in &board.snakes {
-        if snake.id != you.id {
-            let head_dist = manhattan_distance(pos, &snake.body[0]);
-            
-            // Evaluate head-to-head scenarios
-            if head_dist <= 2 {
-                if you.body.len() <= snake.body.len() {
-                    threat_score -= 150.0; // Strong penalty for risky head-to-head
-                } else {
-                    threat_score += 50.0; // Potential to eliminate shorter snake
-                }
+    // `load` with the file contents and the variables passed in
+    fn load_from(path: &str, contents: Option<&str>, env: &EnvLookup) -> Result<Self, String> {
+        let strict = env_flag(env, "STRICT_CONFIG")?.unwrap_or(false);
+        let mut config = match contents.map(Config::from_toml) {
+            Some(Ok(config)) => config,
+            Some(Err(e)) if strict => return Err(format!("invalid config file {path}: {e}")),
+            Some(Err(e)) => {
+                warn!(path = %path, error = %e, "invalid config file, using defaults");
+                Config::default()
             }
+            None => Config::default(),
+        };
+        let errors = config.apply_env(env);
+        if strict && !errors.is_empty() {
+            return Err(errors.join(", "));
+        }
+        for e in errors {
+            warn!(error = %e, "ignoring invalid environment override");
         }
+        Ok(config)
     }
 
-    threat_score
-}
+    fn from_toml(contents: &str) -> Result<Self, String> {
+        let mut config: Config = toml::from_str(contents).map_err(|e| e.to_string())?;
+        config.search_blend = config.search_blend.clamp(0.0, 1.0);
+        Ok(config)
+    }
 
-fn evaluate_center_control(pos: &Coord, board: &Board) -> f64 {
-    let center_x = board.width as f64 / 2.0;
-    let center_y = board.height as f64 / 2.0;
-    let dist_from_center = ((pos.x as f64 - center_x).powi(2) + 
-                           (pos.y as f64 - center_y).powi(2)).sqrt();
-    
-    // Prefer positions closer to center
-    25.0 - dist_from_center * 2.0
-}
+    // Applies the environment overrides, returning the ones that were set but
+    // could not be parsed
+    fn apply_env(&mut self, env: &EnvLookup) -> Vec<String> {
+        let mut errors = Vec::new();
+        noted(env_override(env, &mut self.appearance.author, "SNAKE_AUTHOR"), &mut errors);
+        noted(env_override(env, &mut self.appearance.color, "SNAKE_COLOR"), &mut errors);
+        noted(env_override(env, &mut self.appearance.head, "SNAKE_HEAD"), &mut errors);
+        noted(env_override(env, &mut self.appearance.tail, "SNAKE_TAIL"), &mut errors);
+        noted(env_override(env, &mut self.host, "HOST"), &mut errors);
+        noted(env_override(env, &mut self.port, "PORT"), &mut errors);
+        noted(env_override(env, &mut self.weights.space, "WEIGHT_SPACE"), &mut errors);
+        noted(env_override(env, &mut self.weights.food, "WEIGHT_FOOD"), &mut errors);
+        noted(env_override(env, &mut self.weights.threat, "WEIGHT_THREAT"), &mut errors);
+        noted(env_override(env, &mut self.weights.avoid_larger, "WEIGHT_AVOID_LARGER"), &mut errors);
+        noted(env_override(env, &mut self.weights.center, "WEIGHT_CENTER"), &mut errors);
+        noted(env_override(env, &mut self.weights.center_decay, "WEIGHT_CENTER_DECAY"), &mut errors);
+        noted(env_override(env, &mut self.weights.loop_penalty, "WEIGHT_LOOP_PENALTY"), &mut errors);
+        noted(env_override(env, &mut self.weights.tail_access, "WEIGHT_TAIL_ACCESS"), &mut errors);
+        noted(env_override(env, &mut self.weights.min_space_penalty, "WEIGHT_MIN_SPACE_PENALTY"), &mut errors);
+        noted(env_override(env, &mut self.weights.larger_region, "WEIGHT_LARGER_REGION"), &mut errors);
+        noted(env_override(env, &mut self.weights.cutoff, "WEIGHT_CUTOFF"), &mut errors);
+        noted(env_override(env, &mut self.weights.pressure, "WEIGHT_PRESSURE"), &mut errors);
+        noted(env_override(env, &mut self.weights.corner_trap, "WEIGHT_CORNER_TRAP"), &mut errors);
+        noted(env_override(env, &mut self.weights.food_access, "WEIGHT_FOOD_ACCESS"), &mut errors);
+        noted(env_override(env, &mut self.weights.chokepoint, "WEIGHT_CHOKEPOINT"), &mut errors);
+        noted(env_override(env, &mut self.weights.edge_crowding, "WEIGHT_EDGE_CROWDING"), &mut errors);
+        noted(env_override(env, &mut self.crowding_radius, "CROWDING_RADIUS"), &mut errors);
+        noted(env_override(env, &mut self.weights.mirror, "WEIGHT_MIRROR"), &mut errors);
+        noted(env_override(env, &mut self.weights.flexibility, "WEIGHT_FLEXIBILITY"), &mut errors);
+        noted(env_override(env, &mut self.weights.food_denial, "WEIGHT_FOOD_DENIAL"), &mut errors);
+        noted(env_override(env, &mut self.weights.region_food, "WEIGHT_REGION_FOOD"), &mut errors);
+        noted(env_override(env, &mut self.weights.escape, "WEIGHT_ESCAPE"), &mut errors);
+        noted(env_override(env, &mut self.weights.hazard_quadrant, "WEIGHT_HAZARD_QUADRANT"), &mut errors);
+        noted(env_override(env, &mut self.weights.hazard_cell, "WEIGHT_HAZARD_CELL"), &mut errors);
+        noted(env_override(env, &mut self.hazard_buffer, "HAZARD_BUFFER"), &mut errors);
+        noted(env_override(env, &mut self.weights.spawn_center, "WEIGHT_SPAWN_CENTER"), &mut errors);
+        noted(env_override(env, &mut self.weights.spine, "WEIGHT_SPINE"), &mut errors);
+        if let Some(anticipate) = noted(env_flag(env, "ANTICIPATE_FOOD_SPAWNS"), &mut errors) {
+            self.anticipate_food_spawns = anticipate;
+        }
+        noted(env_override(env, &mut self.box_in_shrink, "BOX_IN_SHRINK"), &mut errors);
+        noted(env_override(env, &mut self.log_sample_rate, "LOG_SAMPLE_RATE"), &mut errors);
+        noted(env_override(env, &mut self.food_denial_health, "FOOD_DENIAL_HEALTH"), &mut errors);
+        if let Some(mirror) = noted(env_flag(env, "MIRROR_OPPONENT"), &mut errors) {
+            self.mirror_opponent = mirror;
+        }
+        if let Some(predictable) = noted(env_flag(env, "PREDICTABLE"), &mut errors) {
+            self.predictable = predictable;
+        }
+        if let Some(safe_tail) = noted(env_flag(env, "SAFE_TAIL"), &mut errors) {
+            self.safe_tail = safe_tail;
+        }
+        if let Some(maximin) = noted(env_flag(env, "MAXIMIN"), &mut errors) {
+            self.maximin = maximin;
+        }
+        if let Some(ignore_case) = noted(env_flag(env, "CASE_INSENSITIVE_IDS"), &mut errors) {
+            self.case_insensitive_ids = ignore_case;
+        }
+        noted(env_override(env, &mut self.food_access_health, "FOOD_ACCESS_HEALTH"), &mut errors);
+        if let Some(growth) = noted(env_flag(env, "MODEL_OPPONENT_GROWTH"), &mut errors) {
+            self.model_opponent_growth = growth;
+        }
+        if let Some(growth) = noted(env_flag(env, "HEAD_TO_HEAD_GROWTH"), &mut errors) {
+            self.head_to_head_growth = growth;
+        }
+        if let Some(floor) = noted(env_parse(env, "MIN_SPACE"), &mut errors) {
+            self.min_space = Some(floor);
+        }
+        if let Some(cap) = noted(env_parse(env, "MAX_MODELED_OPPONENTS"), &mut errors) {
+            self.max_modeled_opponents = Some(cap);
+        }
+        if let Some(cap) = noted(env_parse(env, "FLOOD_FILL_MAX_CELLS"), &mut errors) {
+            self.flood_fill_max_cells = Some(cap);
+        }
+        if let Some(health) = noted(env_parse(env, "DUEL_FOOD_MIN_HEALTH"), &mut errors) {
+            self.duel_food_min_health = Some(health);
+        }
+        if let Some(control) = noted(env_flag(env, "DUEL_FOOD_CONTROL"), &mut errors) {
+            self.duel_food_control = control;
+        }
+        if let Some(distance) = noted(env_parse(env, "FOOD_SAFE_DISTANCE"), &mut errors) {
+            self.food_safe_distance = Some(distance);
+        }
+        if let Some(scaled) = noted(env_flag(env, "ESCAPE_SCALED_THREAT"), &mut errors) {
+            self.escape_scaled_threat = scaled;
+        }
+        if let Some(track) = noted(env_flag(env, "TRACK_PREDICTIONS"), &mut errors) {
+            self.track_predictions = track;
+        }
+        if let Some(separated) = noted(env_flag(env, "SEPARATED_ENDGAME"), &mut errors) {
+            self.separated_endgame = separated;
+        }
+        if let Some(turns) = noted(env_parse(env, "DUEL_OPENING_TURNS"), &mut errors) {
+            self.duel_opening_turns = Some(turns);
+        }
+        if let Some(floor) = noted(env_parse(env, "SPACE_FLOOR"), &mut errors) {
+            self.space_floor = Some(floor);
+        }
+        if let Some(plan) = noted(env_flag(env, "PLAN_FOOD_ROUTE"), &mut errors) {
+            self.plan_food_route = plan;
+        }
+        if let Some(ignore) = noted(env_flag(env, "IGNORE_DEAD_SNAKES"), &mut errors) {
+            self.ignore_dead_snakes = ignore;
+        }
+        if let Some(fill) = noted(env_flag(env, "FILL_CYCLE"), &mut errors) {
+            self.fill_cycle = fill;
+        }
+        if let Some(turns) = noted(env_parse(env, "STALEMATE_TURNS"), &mut errors) {
+            self.stalemate_turns = Some(turns);
+        }
+        noted(env_override(env, &mut self.stalemate_shift, "STALEMATE_SHIFT"), &mut errors);
+        if let Some(wrapped) = noted(env_flag(env, "WRAPPED_DISTANCE"), &mut errors) {
+            self.wrapped_distance = wrapped;
+        }
+        if let Some(debug) = noted(env_flag(env, "DEBUG"), &mut errors) {
+            self.debug = debug;
+        }
+        if let Some(dir) = noted(env_parse(env, "REPLAY_DIR"), &mut errors) {
+            self.replay_dir = Some(dir);
+        }
+        noted(env_override(env, &mut self.search_depth, "SEARCH_DEPTH"), &mut errors);
+        noted(env_override(env, &mut self.max_search_depth, "MAX_SEARCH_DEPTH"), &mut errors);
+        noted(env_override(env, &mut self.search_opponents, "SEARCH_OPPONENTS"), &mut errors);
+        if let Some(path) = noted(env_parse(env, "FALLBACK_LOG"), &mut errors) {
+            self.fallback_log = Some(path);
+        }
+        if let Some(seconds) = noted(env_parse(env, "SELF_CHECK_INTERVAL"), &mut errors) {
+            self.self_check_interval = Some(seconds);
+        }
+        noted(env_override(env, &mut self.latency_margin_ms, "LATENCY_MARGIN_MS"), &mut errors);
+        noted(env_override(env, &mut self.hard_move_cap_ms, "HARD_MOVE_CAP_MS"), &mut errors);
+        if let Some(depth) = noted(env_parse(env, "OPPONENT_DEPTH"), &mut errors) {
+            self.opponent_depth = Some(depth);
+        }
+        noted(env_override(env, &mut self.search_blend, "SEARCH_BLEND"), &mut errors);
+        self.search_blend = self.search_blend.clamp(0.0, 1.0);
+        noted(env_override(env, &mut self.starvation_margin, "STARVATION_MARGIN"), &mut errors);
+        if let Some(worst) = noted(env_flag(env, "ASSUME_WORST_RESOLUTION"), &mut errors) {
+            self.assume_worst_resolution = worst;
+        }
+        if let Some(conservative) = noted(env_flag(env, "CONSERVATIVE_TAILS"), &mut errors) {
+            self.conservative_tails = conservative;
+        }
+        noted(env_override(env, &mut self.coord_origin, "COORD_ORIGIN"), &mut errors);
+        noted(env_override(env, &mut self.personality, "PERSONALITY"), &mut errors);
+        noted(env_override(env, &mut self.aggression_start_turn, "AGGRESSION_START_TURN"), &mut errors);
+        noted(env_override(env, &mut self.head_to_head_margin, "HEAD_TO_HEAD_MARGIN"), &mut errors);
+        if let Some(offload) = noted(env_flag(env, "OFFLOAD_COMPUTE"), &mut errors) {
+            self.offload_compute = offload;
+        }
+        noted(env_override(env, &mut self.fallback_strategy, "FALLBACK_STRATEGY"), &mut errors);
+        noted(env_override(env, &mut self.hazard_space_weight, "HAZARD_SPACE_WEIGHT"), &mut errors);
+        errors
+    }
 
-// Define strategy space for bilinear duel (simplified to 2D for movement directions)
-fn bilinear_duel(state: &GameState) -> String {
-    let you = &state.you;
-    let head = &you.body[0];
-    let board = &state.board;
-    
-    let possible_moves = vec![
-        Move::new("up"),
-        Move::new("down"),
-        Move::new("left"),
-        Move::new("right"),
-    ];
+    // This config with the preset for `source` applied, if there is one
+    fn for_source(&self, source: &str) -> Option<Config> {
+        let preset = self.source_presets.get(source)?;
+        let mut config = self.clone();
+        if let Some(personality) = preset.personality {
+            config.personality = personality;
+        }
+        if let Some(weights) = &preset.weights {
+            config.weights = weights.clone();
+        }
+        Some(config)
+    }
 
-    // Find best move using weighted scoring
-    let best_move = evaluate_moves(possible_moves, head, you, board);
-    best_move.direction
-}
+    // This config playing balanced instead of aggressive, while `turn` is still
+    // before AGGRESSION_START_TURN
+    fn for_turn(&self, turn: i32) -> Option<Config> {
+        if self.personality != Personality::Aggressive || turn >= self.aggression_start_turn {
+            return None;
+        }
+        Some(Config {
+            personality: Personality::Balanced,
+            ..self.clone()
+        })
+    }
 
-fn evaluate_moves(mut moves: Vec<Move>, head: &Coord, you: &Snake, board: &Board) -> Move {
-    for move_option in &mut moves {
-        let new_pos = get_new_position(head, &move_option.direction);
-        
-        // Initialize score
-        let mut score = 0.0;
-        
-        // Immediate death check
-        if !is_safe_move(&new_pos, board, you.body.len()) {
-            move_option.score = f64::NEG_INFINITY;
-            continue;
+    // This config playing for food and space harder, by STALEMATE_SHIFT, once we
+    // have circled without growing for STALEMATE_TURNS turns
+    fn for_stalemate(&self, memory: &GameMemory) -> Option<Config> {
+        let turns = self.stalemate_turns?;
+        if memory.stalled_turns < turns {
+            return None;
+        }
+        let mut config = self.clone();
+        config.weights.food *= self.stalemate_shift;
+        config.weights.space *= self.stalemate_shift;
+        Some(config)
+    }
+
+    // This config measuring distances on a torus, in a "wrapped" game with
+    // WRAPPED_DISTANCE on
+    fn for_ruleset(&self, ruleset: &Ruleset) -> Option<Config> {
+        if !self.wrapped_distance || ruleset.name != "wrapped" {
+            return None;
         }
+        Some(Config {
+            toroidal: true,
+            ..self.clone()
+        })
+    }
 
-        // Space evaluation (weighted highest)
-        let mut visited = Vec::new();
-        let available_space = flood_fill(board, &new_pos, &mut visited);
-        score += available_space as f64 * 5.0; // High weight for available space
+    // Grid distance between two cells, going around the edges on a wrapped board
+    fn distance(&self, a: &Coord, b: &Coord, board: &Board) -> i32 {
+        if self.toroidal {
+            wrapped_distance(a, b, board)
+        } else {
+            manhattan_distance(a, b)
+        }
+    }
 
-        // Food evaluation
-        if let Some((food_dist, food_dir)) = evaluate_food(&new_pos, board, you.health) {
-            let food_score = calculate_food_score(food_dist, you.health);
-            if move_option.direction == food_dir {
-                score += food_score;
-            }
+    fn flood_fill_cap(&self, board: &Board) -> usize {
+        self.flood_fill_max_cells
+            .unwrap_or((board.width.max(0) * board.height.max(0)) as usize)
+    }
+
+    // Heavy penalty when reachable space falls under the floor, so such a move
+    // only wins when every alternative is just as cramped
+    fn space_floor_penalty(&self, space: i32, you: &Snake) -> f64 {
+        let floor = self.min_space.unwrap_or(you.body.len());
+        if (space.max(0) as usize) < floor {
+            -self.weights.min_space_penalty
+        } else {
+            0.0
+        }
+    }
+
+    fn food_weight(&self) -> f64 {
+        match self.personality {
+            Personality::MaximizeLength => self.weights.food * 5.0,
+            _ => self.weights.food,
+        }
+    }
+
+    // Multiplier on the threat and larger-snake avoidance terms
+    fn threat_scale(&self) -> f64 {
+        match self.personality {
+            Personality::MaximizeLength => 0.2,
+            _ => 1.0,
         }
+    }
 
-        // Threat evaluation
-        score += evaluate_threats(&new_pos, board, you);
+    fn center_weight(&self, turn: i32) -> f64 {
+        self.weights.center * (-self.weights.center_decay * turn.max(0) as f64).exp()
+    }
 
-        // Center control evaluation
-        score += evaluate_center_control(&new_pos, board);
+    fn modeled_opponents(&self) -> usize {
+        self.max_modeled_opponents.unwrap_or(usize::MAX)
+    }
 
-        move_option.score = score;
+    fn opponent_depth(&self) -> u32 {
+        self.opponent_depth.unwrap_or(self.search_depth)
     }
 
-    // Sort by score and return best move
-    moves.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
-    moves.into_iter().next().unwrap_or(Move::new("up"))
+    // Whether we are long enough to initiate a head-to-head against `opponent`,
+    // counting the segment it gains from food next to its head with HEAD_TO_HEAD_GROWTH
+    fn wins_head_on(&self, you: &Snake, opponent: &Snake, board: &Board) -> bool {
+        let growth = (self.head_to_head_growth && next_to_food(opponent, board)) as usize;
+        you.body.len() >= opponent.body.len() + growth + self.head_to_head_margin
+    }
 }
 
-// Add these after your existing struct definitions
-#[derive(Clone, Eq, PartialEq)]
-struct Point {
-    x: i32,
-    y: i32,
+// Looks up an environment variable; tests pass a map instead of the process environment
+type EnvLookup = dyn Fn(&str) -> Option<String>;
+
+fn process_env(key: &str) -> Option<String> {
+    std::env::var(key).ok()
 }
 
-impl Point {
-    fn from_coord(coord: &Coord) -> Self {
-        Point {
-            x: coord.x,
-            y: coord.y,
-        }
+// The variable parsed, None when it is unset, an error when it is set but malformed
+fn env_parse<T: std::str::FromStr>(env: &EnvLookup, key: &str) -> Result<Option<T>, String> {
+    let Some(value) = env(key) else {
+        return Ok(None);
+    };
+    match value.trim().parse() {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(_) => Err(format!("invalid {key}: {value:?}")),
+    }
+}
+
+fn env_override<T: std::str::FromStr>(env: &EnvLookup, target: &mut T, key: &str) -> Result<(), String> {
+    if let Some(value) = env_parse(env, key)? {
+        *target = value;
+    }
+    Ok(())
+}
+
+fn env_flag(env: &EnvLookup, key: &str) -> Result<Option<bool>, String> {
+    let Some(value) = env(key) else {
+        return Ok(None);
+    };
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(Some(true)),
+        "0" | "false" | "no" | "off" => Ok(Some(false)),
+        _ => Err(format!("invalid {key}: {value:?}")),
     }
 }
 
-// Add this new function
-fn flood_fill(board: &Board, start: &Coord, visited: &mut Vec<Point>) -> i32 {
-    let mut stack = vec![Point::from_coord(start)];
-    let mut space_count = 0;
-    let directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+// Collects a malformed override into `errors` and carries on as if it were unset
+fn noted<T: Default>(result: Result<T, String>, errors: &mut Vec<String>) -> T {
+    result.unwrap_or_else(|e| {
+        errors.push(e);
+        T::default()
+    })
+}
 
-    while let Some(current) = stack.pop() {
-        if visited.contains(&current) {
-            continue;
+// Serializes to the same lowercase strings as `as_str`
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn all() -> [Direction; 4] {
+        [Direction::Up, Direction::Down, Direction::Left, Direction::Right]
+    }
+
+    // The string the Battlesnake API expects
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Up => "up",
+            Direction::Down => "down",
+            Direction::Left => "left",
+            Direction::Right => "right",
         }
+    }
 
-        // Check if position is valid
-        if current.x < 0
-            || current.x >= board.width
-            || current.y < 0
-            || current.y >= board.height
-        {
-            continue;
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
         }
+    }
+}
 
-        // Check for collision with snake bodies
-        let is_snake = board.snakes.iter().any(|snake| {
-            snake.body.iter().any(|segment| {
-                segment.x == current.x && segment.y == current.y
-            })
-        });
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
-        if is_snake {
-            continue;
-        }
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Direction::all()
+            .into_iter()
+            .find(|dir| dir.as_str() == value)
+            .ok_or_else(|| format!("unknown direction: {value}"))
+    }
+}
 
-        visited.push(current.clone());
-        space_count += 1;
+// Move this near other struct definitions
+#[derive(Clone, Debug)]
+struct Move {
+    direction: Direction,
+    score: f64,
+}
 
-        // Add adjacent cells to stack
-        for (dx, dy) in directions.iter() {
-            stack.push(Point {
-                x: current.x + dx,
-                y: current.y + dy,
-            });
+impl Move {
+    fn new(direction: Direction) -> Self {
+        Move {
+            direction,
+            score: 0.0,
         }
     }
+}
 
-    space_count
+// Number of past head positions remembered per game for loop detection
+const HEAD_HISTORY_LEN: usize = 8;
+
+// What we remember about a game between turns
+#[derive(Clone, Debug, Default)]
+struct GameMemory {
+    // Our most recent head positions, oldest first
+    recent_heads: VecDeque<Coord>,
+    // Space reachable from our head at the start of the previous turn
+    last_space: Option<i32>,
+    // Food our last move was heading for
+    food_target: Option<Coord>,
+    // Where we expected each opponent's head to be this turn, by snake id
+    // (TRACK_PREDICTIONS)
+    predicted_heads: HashMap<String, Coord>,
+    // Our length last turn, and how many turns in a row we have since retraced
+    // our recent path without growing
+    last_length: Option<usize>,
+    stalled_turns: u32,
 }
 
-// Добавьте эту функцию для проверки безопасности хода
-fn is_move_safe(new_pos: &Coord, board: &Board, snake_length: usize) -> bool {
-    // Проверка на выход за пределы поля
-    if new_pos.x < 0 || new_pos.x >= board.width || new_pos.y < 0 || new_pos.y >= board.height {
-        return false;
+impl GameMemory {
+    fn record_head(&mut self, head: &Coord) {
+        if self.recent_heads.len() == HEAD_HISTORY_LEN {
+            self.recent_heads.pop_front();
+        }
+        self.recent_heads.push_back(head.clone());
     }
 
-    // Проверка столкновений со змеями
-    for snake in &board.snakes {
-        for (i, segment) in snake.body.iter().enumerate() {
-            // Пропускаем последний сегмент хвоста, так как он движется
-            if i == snake.body.len() - 1 && snake.body.len() == snake_length {
+    fn visited_recently(&self, cell: &Coord) -> bool {
+        self.recent_heads.contains(cell)
+    }
+
+    // Call before `record_head`, so the revisit check looks at earlier turns only
+    fn record_progress(&mut self, head: &Coord, length: usize) {
+        let grew = self.last_length.is_some_and(|last| length > last);
+        if !grew && self.visited_recently(head) {
+            self.stalled_turns += 1;
+        } else {
+            self.stalled_turns = 0;
+        }
+        self.last_length = Some(length);
+    }
+
+    fn record_space(&mut self, space: i32) {
+        self.last_space = Some(space);
+    }
+
+    // Everything remembered from a turn once its move is chosen: where our head
+    // was, how much room it had and the food we went for
+    fn record_turn(&mut self, you: &Snake, space: i32, target: Option<Coord>) {
+        if let Some(head) = you.body.first() {
+            self.record_progress(head, you.body.len());
+            self.record_head(head);
+            self.record_space(space);
+        }
+        self.food_target = target;
+    }
+
+    // How many of last turn's predictions came true on `board`, out of those
+    // whose snake is still alive to check
+    fn score_predictions(&self, board: &Board) -> (u64, u64) {
+        let mut hits = 0;
+        let mut checked = 0;
+        for snake in &board.snakes {
+            let (Some(predicted), Some(head)) = (self.predicted_heads.get(&snake.id), snake.body.first()) else {
                 continue;
-            }
-            if new_pos.x == segment.x && new_pos.y == segment.y {
-                return false;
-            }
+            };
+            checked += 1;
+            hits += (predicted == head) as u64;
         }
+        (hits, checked)
     }
 
-    true
+    // Whether our space shrank by more than `shrink` (a fraction) since last turn,
+    // the sign of an opponent closing a box around us
+    fn boxed_in(&self, space: i32, shrink: f64) -> bool {
+        self.last_space
+            .is_some_and(|last| (space as f64) < last as f64 * (1.0 - shrink))
+    }
+}
+
+// State shared by all workers
+#[derive(Default)]
+struct AppState {
+    games: Mutex<HashMap<String, GameMemory>>,
+    metrics: Mutex<Metrics>,
+    // Picks the turns that get detailed logs (LOG_SAMPLE_RATE)
+    log_sampler: Mutex<Rng>,
+}
+
+thread_local! {
+    // Whether the decision running on this thread emits its per-move score breakdown
+    static DETAILED_LOGS: Cell<bool> = const { Cell::new(true) };
 }
 
-// Обновленная функция compute_payoff
-fn compute_payoff(my_move: &str, _opp_move: &str, head: &Coord, board: &Board) -> f64 {
-    let new_pos = match my_move {
-        "up" => Coord { x: head.x, y: head.y + 1 },
-        "down" => Coord { x: head.x, y: head.y - 1 },
-        "left" => Coord { x: head.x - 1, y: head.y },
-        "right" => Coord { x: head.x + 1, y: head.y },
-        _ => return -100.0,
+// A debug! line that is part of the score breakdown, skipped on turns left out by LOG_SAMPLE_RATE
+macro_rules! detail {
+    ($($arg:tt)*) => {
+        if DETAILED_LOGS.with(Cell::get) {
+            debug!($($arg)*);
+        }
     };
+}
+
+// Counters since startup, served by /metrics
+#[derive(Serialize, Clone, Default)]
+struct Metrics {
+    games_started: u64,
+    games_ended: u64,
+    // Games that ended with us the last snake standing, and with us eliminated
+    wins: u64,
+    losses: u64,
+    moves: u64,
+    fallback_moves: u64,
+    // Moves where the search and later adjustments overruled the one-move evaluation's pick
+    search_overrides: u64,
+    // Time spent deciding, summed over all moves and the slowest single move
+    total_latency_ms: f64,
+    max_latency_ms: f64,
+    // Opponent moves checked against our model's prediction, and how many it got right
+    // (TRACK_PREDICTIONS)
+    predictions: u64,
+    prediction_hits: u64,
+}
 
-    // Check for immediate death
-    if !is_move_safe(&new_pos, board, board.snakes[0].body.len()) {
-        return -100.0;
+// Add this helper function early in the file
+fn get_new_position(head: &Coord, direction: Direction) -> Coord {
+    // Bottom-left origin; COORD_ORIGIN is applied to the answer (see CoordOrigin::engine_direction)
+    match direction {
+        Direction::Up => Coord { x: head.x, y: head.y + 1 },
+        Direction::Down => Coord { x: head.x, y: head.y - 1 },
+        Direction::Left => Coord { x: head.x - 1, y: head.y },
+        Direction::Right => Coord { x: head.x + 1, y: head.y },
     }
+}
 
-    let mut score = 0.0;
+fn manhattan_distance(a: &Coord, b: &Coord) -> i32 {
+    (a.x - b.x).abs() + (a.y - b.y).abs()
+}
 
-    // Space evaluation
-    let mut visited = Vec::new();
-    let available_space = flood_fill(board, &new_pos, &mut visited);
-    score += available_space as f64 * 5.0;
+// Manhattan distance on a board whose edges connect: on each axis the shorter of
+// the direct gap and the gap going around
+fn wrapped_distance(a: &Coord, b: &Coord, board: &Board) -> i32 {
+    let dx = (a.x - b.x).abs();
+    let dy = (a.y - b.y).abs();
+    dx.min(board.width - dx).max(0) + dy.min(board.height - dy).max(0)
+}
 
-    // Food evaluation
-    if let Some((food_distance, _)) = evaluate_food(&new_pos, board, board.snakes[0].health) {
-        score += 100.0 - food_distance as f64;  // Removed unnecessary parentheses
+// Where a wrapped game puts a cell that stepped off one edge: back on at the opposite one
+fn wrap_coord(coord: &Coord, board: &Board) -> Coord {
+    Coord {
+        x: coord.x.rem_euclid(board.width.max(1)),
+        y: coord.y.rem_euclid(board.height.max(1)),
     }
+}
 
-    score
+// The cell one step from `from`, across the edges when the board is `toroidal`
+fn neighbour(from: &Coord, dir: Direction, board: &Board, toroidal: bool) -> Coord {
+    let next = get_new_position(from, dir);
+    if toroidal {
+        wrap_coord(&next, board)
+    } else {
+        next
+    }
 }
 
-// API endpoints
-async fn index() -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({
-        "apiversion": "1",
-        "author": "Dumas",
-        "color": "#FF0000",
-        "head": "default",
-        "tail": "default",
-    }))
+// `direction_between`, also counting steps across the edges of a `toroidal` board
+fn step_direction(from: &Coord, to: &Coord, board: &Board, toroidal: bool) -> Option<Direction> {
+    Direction::all()
+        .into_iter()
+        .find(|dir| neighbour(from, *dir, board, toroidal) == *to)
 }
 
-async fn start(_state: web::Json<GameState>) -> HttpResponse {
-    HttpResponse::Ok().json(StartResponse {
-        color: "#FF0000".to_string(),
-    })
+// Direction of a single step between two adjacent cells
+fn direction_between(from: &Coord, to: &Coord) -> Option<Direction> {
+    Direction::all()
+        .into_iter()
+        .find(|dir| get_new_position(from, *dir) == *to)
 }
 
-async fn r#move(state: web::Json<GameState>) -> HttpResponse {
-    let chosen_move = bilinear_duel(&state);
-    HttpResponse::Ok().json(MoveResponse {
-        r#move: chosen_move,
-    })
+// Direction the snake moved last turn, None while its segments are still stacked
+fn heading(snake: &Snake) -> Option<Direction> {
+    let head = snake.body.first()?;
+    let neck = snake.body.iter().find(|segment| *segment != head)?;
+    direction_between(neck, head)
 }
 
-async fn end(_state: web::Json<GameState>) -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({}))
+// The move that would turn the head back into the neck
+fn reverse_direction(snake: &Snake) -> Option<Direction> {
+    heading(snake).map(Direction::opposite)
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
-        App::new()
-            .route("/", web::get().to(index))
-            .route("/start", web::post().to(start))
-            .route("/move", web::post().to(r#move))
-            .route("/end", web::post().to(end))
+// Cost of stepping onto a cell: hazards cost their health damage on top of the move itself
+fn hazard_step_cost<'a>(board: &'a Board, ruleset: &Ruleset) -> impl Fn(&Coord) -> i32 + 'a {
+    let damage = ruleset.settings.hazard_damage_per_turn.max(0);
+    move |cell: &Coord| if board.hazards.contains(cell) { 1 + damage } else { 1 }
+}
+
+// A* search from `from` to `to` avoiding walls and snake bodies, with `step_cost`
+// giving the cost of entering each cell (at least 1, which keeps the Manhattan
+// heuristic admissible). On a `toroidal` board paths may cross the edges.
+// Returns the path without the starting cell, or None if `to` is unreachable.
+fn a_star(board: &Board, from: &Coord, to: &Coord, toroidal: bool, step_cost: impl Fn(&Coord) -> i32) -> Option<Vec<Coord>> {
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Coord, Coord> = HashMap::new();
+    let mut g_score: HashMap<Coord, i32> = HashMap::new();
+    let estimate = |cell: &Coord| {
+        if toroidal {
+            wrapped_distance(cell, to, board)
+        } else {
+            manhattan_distance(cell, to)
+        }
+    };
+
+    g_score.insert(from.clone(), 0);
+    open.push(Reverse((estimate(from), 0, from.x, from.y)));
+
+    while let Some(Reverse((_, g, x, y))) = open.pop() {
+        let current = Coord { x, y };
+        if current == *to {
+            let mut path = vec![current.clone()];
+            let mut node = current;
+            while let Some(prev) = came_from.get(&node) {
+                if prev == from {
+                    break;
+                }
+                path.push(prev.clone());
+                node = prev.clone();
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if g > *g_score.get(&current).unwrap_or(&i32::MAX) {
+            continue;
+        }
+
+        for dir in Direction::all() {
+            let next = neighbour(&current, dir, board, toroidal);
+            if next.x < 0 || next.x >= board.width || next.y < 0 || next.y >= board.height {
+                continue;
+            }
+            // The target itself may be occupied (e.g. a tail we are chasing)
+            if next != *to && !is_move_safe(&next, board) {
+                continue;
+            }
+
+            let tentative = g + step_cost(&next).max(1);
+            if tentative < *g_score.get(&next).unwrap_or(&i32::MAX) {
+                g_score.insert(next.clone(), tentative);
+                came_from.insert(next.clone(), current.clone());
+                open.push(Reverse((tentative + estimate(&next), tentative, next.x, next.y)));
+            }
+        }
+    }
+
+    None
+}
+
+// First move along the shortest safe path, or None if `to` is unreachable
+fn first_step_of_path(
+    board: &Board,
+    from: &Coord,
+    to: &Coord,
+    toroidal: bool,
+    step_cost: impl Fn(&Coord) -> i32,
+) -> Option<Direction> {
+    let path = a_star(board, from, to, toroidal, step_cost)?;
+    let first = path.first()?;
+    step_direction(from, first, board, toroidal)
+}
+
+// Closest food by path length, with the A* path leading to it
+fn nearest_reachable_food(head: &Coord, board: &Board, ruleset: &Ruleset, config: &Config) -> Option<(Coord, Vec<Coord>)> {
+    let step_cost = hazard_step_cost(board, ruleset);
+    board
+        .food
+        .iter()
+        .filter_map(|food| a_star(board, head, food, config.toroidal, &step_cost).map(|path| (food.clone(), path)))
+        .min_by_key(|(_, path)| path.len())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Hunger {
+    Fed,
+    // Health is within the safety margin of what the nearest food costs to reach
+    Urgent,
+    // No food can be reached before we starve
+    Desperate,
+}
+
+// Food items a planned route considers, nearest first, to keep the planning cheap
+const ROUTE_FOOD_LIMIT: usize = 4;
+
+// Steps of a route collecting the nearby food: starting from each candidate in
+// turn and chaining to the nearest remaining one by A*, the chain that collects
+// the most food for the least health. Empty when no food is reachable.
+fn plan_food_route(board: &Board, you: &Snake, ruleset: &Ruleset, config: &Config) -> Vec<Direction> {
+    let Some(head) = you.body.first() else {
+        return Vec::new();
+    };
+    let step_cost = hazard_step_cost(board, ruleset);
+    let cost = |path: &[Coord]| path.iter().map(&step_cost).sum::<i32>();
+    let mut candidates: Vec<(Coord, Vec<Coord>)> = board
+        .food
+        .iter()
+        .filter_map(|food| a_star(board, head, food, config.toroidal, &step_cost).map(|path| (food.clone(), path)))
+        .collect();
+    candidates.sort_by_key(|(_, path)| cost(path));
+    candidates.truncate(ROUTE_FOOD_LIMIT);
+
+    let mut best: Option<(usize, i32, Vec<Coord>)> = None;
+    for (first, path) in &candidates {
+        let mut route = path.clone();
+        let mut remaining: Vec<&Coord> = candidates.iter().map(|(food, _)| food).filter(|food| *food != first).collect();
+        let mut at = first.clone();
+        let mut collected = 1;
+        while let Some((index, leg)) = remaining
+            .iter()
+            .enumerate()
+            .filter_map(|(index, food)| a_star(board, &at, food, config.toroidal, &step_cost).map(|leg| (index, leg)))
+            .min_by_key(|(_, leg)| cost(leg))
+        {
+            at = remaining.remove(index).clone();
+            route.extend(leg);
+            collected += 1;
+        }
+        let total = cost(&route);
+        let better = best.as_ref().is_none_or(|(most, least, _)| collected > *most || (collected == *most && total < *least));
+        if better {
+            best = Some((collected, total, route));
+        }
+    }
+
+    let Some((_, _, route)) = best else {
+        return Vec::new();
+    };
+    std::iter::once(head)
+        .chain(route.iter())
+        .zip(route.iter())
+        .filter_map(|(from, to)| step_direction(from, to, board, config.toroidal))
+        .collect()
+}
+
+// Compares our health with the health it costs (moves plus hazard damage) to reach the nearest food
+fn hunger(head: &Coord, you: &Snake, board: &Board, ruleset: &Ruleset, config: &Config) -> Hunger {
+    // With no food on the board there is nothing to take risks for, so don't
+    // let hunger switch off the other terms
+    if board.food.is_empty() {
+        return Hunger::Fed;
+    }
+    let step_cost = hazard_step_cost(board, ruleset);
+    let health_needed = nearest_reachable_food(head, board, ruleset, config)
+        .map(|(_, path)| path.iter().map(&step_cost).sum::<i32>());
+    let margin = config.starvation_margin;
+
+    match health_needed {
+        Some(needed) if you.health >= needed + margin => Hunger::Fed,
+        Some(needed) if you.health >= needed => Hunger::Urgent,
+        // Unreachable food only matters once we are running low
+        None if you.health > margin => Hunger::Fed,
+        _ => Hunger::Desperate,
+    }
+}
+
+// Bonuses (in moves of path length) for food with room around it and for food near the center
+const FOOD_SPACE_BONUS: f64 = 3.0;
+const FOOD_POSITION_BONUS: f64 = 2.0;
+// Bonus (in moves of path length) per board-fraction of Voronoi control a 1v1
+// food would give us, with DUEL_FOOD_CONTROL
+const DUEL_CONTROL_BONUS: f64 = 10.0;
+
+// How much a piece of food is worth going for: path length still dominates, but
+// between similar distances prefer food that leaves room after eating and sits
+// in a strong position. `room` is the size of the region around the food.
+fn food_desirability(food: &Coord, path_len: usize, room: usize, board: &Board) -> f64 {
+    let area = (board.width * board.height).max(1) as f64;
+    let space_after = room as f64 / area;
+    let position = evaluate_center_control(food, board).max(0.0) / 25.0;
+    -(path_len as f64) + FOOD_SPACE_BONUS * space_after + FOOD_POSITION_BONUS * position
+}
+
+// Most desirable reachable food as an effective distance, the first step towards
+// it and the food itself
+fn evaluate_food(head: &Coord, board: &Board, you: &Snake, ruleset: &Ruleset, config: &Config) -> Option<(f64, Direction, Coord)> {
+    let step_cost = hazard_step_cost(board, ruleset);
+    let area = (board.width * board.height).max(1) as f64;
+    let rival = duel_rival(board, you, config);
+    // Food in the same region leaves the same room after eating: flood each region once
+    let mut regions: Vec<HashMap<Coord, i32>> = Vec::new();
+    let best_food = board
+        .food
+        .iter()
+        .filter_map(|food| {
+            let path = a_star(board, head, food, config.toroidal, &step_cost)?;
+            let room = match regions.iter().find(|region| region.contains_key(food)) {
+                Some(region) => region.len(),
+                None => {
+                    regions.push(bfs_distances(board, food));
+                    regions[regions.len() - 1].len()
+                }
+            };
+            let mut desirability = food_desirability(food, path.len(), room, board);
+            // In a duel, food is also a position: favour food that would put us
+            // ahead in the race for cells
+            if let Some(their_head) = rival {
+                desirability += DUEL_CONTROL_BONUS * space_differential(board, food, their_head) as f64 / area;
+            }
+            Some((food, desirability))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .and_then(|(food, desirability)| {
+            first_step_of_path(board, head, food, config.toroidal, &step_cost).map(|dir| (-desirability, dir, food.clone()))
+        });
+
+    // Adjust score based on health
+    best_food.map(|(dist, dir, food)| {
+        let urgency = if you.health < 25 { 1.5 } else { 1.0 };
+        (dist * urgency, dir, food)
     })
-    .bind("0.0.0.0:8000")?
-    .run()
-    .await
+}
+
+// The opponent's head in a 1v1 when DUEL_FOOD_CONTROL is on
+fn duel_rival<'a>(board: &'a Board, you: &Snake, config: &Config) -> Option<&'a Coord> {
+    if !config.duel_food_control || board.snakes.len() != 2 {
+        return None;
+    }
+    board.snakes.iter().find(|snake| snake.id != you.id)?.body.first()
+}
+
+// The single opponent in a 1v1 when both snakes are healthy enough to play for space
+fn duel_food_opponent<'a>(board: &'a Board, you: &Snake, config: &Config) -> Option<&'a Snake> {
+    let min_health = config.duel_food_min_health?;
+    if board.snakes.len() != 2 || you.health <= min_health {
+        return None;
+    }
+    board
+        .snakes
+        .iter()
+        .find(|snake| snake.id != you.id && snake.health > min_health && !snake.body.is_empty())
+}
+
+// Whether stepping from `from` to `pos` closes in on a strictly longer opponent's
+// head and leaves us nearer to it than FOOD_SAFE_DISTANCE
+fn nears_larger_opponent(pos: &Coord, from: &Coord, board: &Board, you: &Snake, config: &Config) -> bool {
+    let Some(safe_distance) = config.food_safe_distance else {
+        return false;
+    };
+    board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id && snake.body.len() > you.body.len())
+        .filter_map(|snake| snake.body.first())
+        .any(|their_head| {
+            let after = config.distance(pos, their_head, board);
+            after < safe_distance && after < config.distance(from, their_head, board)
+        })
+}
+
+// Breadth-first distances from `start` over cells that are safe to enter
+fn bfs_distances(board: &Board, start: &Coord) -> HashMap<Coord, i32> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    distances.insert(start.clone(), 0);
+    queue.push_back(start.clone());
+
+    while let Some(current) = queue.pop_front() {
+        let dist = distances[&current];
+        for dir in Direction::all() {
+            let next = get_new_position(&current, dir);
+            if distances.contains_key(&next) || !is_move_safe(&next, board) {
+                continue;
+            }
+            distances.insert(next.clone(), dist + 1);
+            queue.push_back(next);
+        }
+    }
+
+    distances
+}
+
+// Cells we reach strictly first minus cells the opponent reaches strictly first
+fn space_differential(board: &Board, ours: &Coord, theirs: &Coord) -> i32 {
+    let our_dist = bfs_distances(board, ours);
+    let their_dist = bfs_distances(board, theirs);
+
+    let mut differential = 0;
+    for (cell, dist) in &our_dist {
+        if their_dist.get(cell).is_none_or(|theirs| dist < theirs) {
+            differential += 1;
+        }
+    }
+    for (cell, dist) in &their_dist {
+        if our_dist.get(cell).is_none_or(|ours| dist < ours) {
+            differential -= 1;
+        }
+    }
+    differential
+}
+
+// Food weight multipliers from relative length: the shortest snake loses every
+// head-to-head and needs to catch up, the longest can afford to play for space
+const SHORTEST_FOOD_DRIVE: f64 = 2.0;
+const LONGEST_FOOD_DRIVE: f64 = 0.5;
+
+fn length_food_drive(board: &Board, you: &Snake) -> f64 {
+    let others: Vec<usize> = board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id)
+        .map(|snake| snake.body.len())
+        .collect();
+    if others.is_empty() {
+        1.0
+    } else if others.iter().all(|len| you.body.len() <= *len) {
+        SHORTEST_FOOD_DRIVE
+    } else if others.iter().all(|len| you.body.len() > *len) {
+        LONGEST_FOOD_DRIVE
+    } else {
+        1.0
+    }
+}
+
+fn calculate_food_score(distance: f64, health: i32) -> f64 {
+    let base_score = 100.0 - distance;
+    
+    // Increase urgency when health is low
+    let score = if health < 25 {
+        base_score * 3.0
+    } else if health < 50 {
+        base_score * 1.5
+    } else {
+        base_score
+    };
+    sanitize(score)
+}
+
+// Bound for any single evaluation term, so one bad term cannot swamp the total
+const SCORE_BOUND: f64 = 1_000_000_000.0;
+
+// Replaces NaN with a neutral 0 and clamps infinities to finite bounds
+fn sanitize(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score.clamp(-SCORE_BOUND, SCORE_BOUND)
+    }
+}
+
+fn evaluate_threats(pos: &Coord, board: &Board, you: &Snake, config: &Config) -> f64 {
+    let mut threat_score = 0.0;
+    let mut escape = None;
+
+    for snake in nearest_opponents(board, you, config.modeled_opponents(), config) {
+        // A snake with no safe move dies next turn whatever it does, so it can't
+        // pick a head-to-head with us either
+        if !safe_moves(snake, board).is_empty() {
+            let head_dist = config.distance(pos, &snake.body[0], board);
+            
+            // Evaluate head-to-head scenarios
+            if head_dist <= 2 {
+                if !config.wins_head_on(you, snake, board) {
+                    let scale = if config.escape_scaled_threat {
+                        *escape.get_or_insert_with(|| escape_scale(pos, board, you, config))
+                    } else {
+                        1.0
+                    };
+                    threat_score -= 150.0 * scale; // Strong penalty for risky head-to-head
+                } else {
+                    threat_score += 50.0; // Potential to eliminate shorter snake
+                }
+            }
+        }
+    }
+
+    sanitize(threat_score)
+}
+
+// Open cells next to `pos` we could move on to
+fn safe_move_count(pos: &Coord, board: &Board) -> usize {
+    Direction::all()
+        .into_iter()
+        .filter(|dir| is_move_safe(&get_new_position(pos, *dir), board))
+        .count()
+}
+
+// How much a losing head-to-head near `pos` should worry us: less when there is
+// room and more than one way to slip past, more when cramped
+fn escape_scale(pos: &Coord, board: &Board, you: &Snake, config: &Config) -> f64 {
+    let exits = safe_move_count(pos, board);
+    let roomy = reachable_space(board, pos, config) as usize >= 2 * you.body.len();
+    match (exits, roomy) {
+        (3.., true) => 0.5,
+        (2, true) => 0.75,
+        (0..=1, _) => 1.5,
+        _ => 1.25,
+    }
+}
+
+// Insurance for later meals: once health is declining, prefer positions from
+// which at least one food can still be reached
+fn food_access(pos: &Coord, board: &Board, you: &Snake, config: &Config) -> f64 {
+    if board.food.is_empty() || you.health >= config.food_access_health {
+        return 0.0;
+    }
+    let reachable = bfs_distances(board, pos);
+    if board.food.iter().any(|food| reachable.contains_key(food)) {
+        config.weights.food_access / 2.0
+    } else {
+        -config.weights.food_access
+    }
+}
+
+// In aggressive mode, rewards our head standing on the shortest path between a
+// hungry opponent's head and its nearest food, so it has to detour while starving
+fn food_denial_score(board: &Board, you: &Snake, config: &Config) -> f64 {
+    if config.personality != Personality::Aggressive {
+        return 0.0;
+    }
+    let Some(pos) = you.body.first() else {
+        return 0.0;
+    };
+    let hungry: Vec<&Coord> = board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id && snake.health <= config.food_denial_health)
+        .filter_map(|snake| snake.body.first())
+        .collect();
+    if hungry.is_empty() {
+        return 0.0;
+    }
+
+    // Their paths as planned before our head got in the way
+    let mut before = board.clone();
+    for snake in before.snakes.iter_mut().filter(|snake| snake.id == you.id) {
+        snake.body.remove(0);
+    }
+    let blocked_paths = hungry
+        .into_iter()
+        .filter(|head| {
+            before
+                .food
+                .iter()
+                .filter_map(|food| a_star(&before, head, food, config.toroidal, |_| 1))
+                .min_by_key(|path| path.len())
+                .is_some_and(|path| path.contains(pos))
+        })
+        .count();
+    blocked_paths as f64 * config.weights.food_denial
+}
+
+// Royale hazards grow in from the edges, so the quadrant that already holds the
+// most of them is the one that closes first: penalize cells by the hazard density
+// of their quadrant
+fn hazard_quadrant_penalty(pos: &Coord, board: &Board, ruleset: &Ruleset, config: &Config) -> f64 {
+    if ruleset.name != "royale" || board.hazards.is_empty() {
+        return 0.0;
+    }
+    let quadrant = |cell: &Coord| (cell.x * 2 >= board.width, cell.y * 2 >= board.height);
+    let ours = quadrant(pos);
+    let hazards = board.hazards.iter().filter(|hazard| quadrant(hazard) == ours).count();
+    let quadrant_area = (board.width * board.height).max(4) as f64 / 4.0;
+    -(hazards as f64 / quadrant_area).min(1.0) * config.weights.hazard_quadrant
+}
+
+// Penalty for ending a move on a hazard cell, or a smaller one for ending next
+// to one, where the next shrink or a squeeze can force us in (royale only)
+fn hazard_proximity_penalty(pos: &Coord, board: &Board, ruleset: &Ruleset, config: &Config) -> f64 {
+    if ruleset.name != "royale" || board.hazards.is_empty() {
+        return 0.0;
+    }
+    if board.hazards.contains(pos) {
+        return -config.weights.hazard_cell;
+    }
+    let beside_hazard = Direction::all()
+        .into_iter()
+        .any(|dir| board.hazards.contains(&get_new_position(pos, dir)));
+    if beside_hazard {
+        -config.weights.hazard_cell * config.hazard_buffer
+    } else {
+        0.0
+    }
+}
+
+// How stretched out our body is: the half perimeter of its bounding box per
+// segment, about 1 for a straight line and down towards 2 / sqrt(length) for a
+// tight coil
+fn body_spread(you: &Snake) -> f64 {
+    let body = &you.body;
+    let min_x = body.iter().map(|cell| cell.x).min().unwrap_or(0);
+    let max_x = body.iter().map(|cell| cell.x).max().unwrap_or(0);
+    let min_y = body.iter().map(|cell| cell.y).min().unwrap_or(0);
+    let max_y = body.iter().map(|cell| cell.y).max().unwrap_or(0);
+    (max_x - min_x + max_y - min_y + 2) as f64 / body.len() as f64
+}
+
+// Edges leave fewer ways out, which only matters with opponents around: penalize
+// an edge cell once per opponent head within CROWDING_RADIUS
+fn edge_crowding(pos: &Coord, board: &Board, you: &Snake, config: &Config) -> f64 {
+    let on_edge = pos.x == 0 || pos.y == 0 || pos.x == board.width - 1 || pos.y == board.height - 1;
+    if !on_edge {
+        return 0.0;
+    }
+    let nearby = board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id)
+        .filter_map(|snake| snake.body.first())
+        .filter(|head| config.distance(pos, head, board) <= config.crowding_radius)
+        .count();
+    -(nearby as f64) * config.weights.edge_crowding
+}
+
+// Cells around a larger snake's head where we feel its pull
+const AVOIDANCE_RADIUS: i32 = 4;
+
+// Repulsion from the heads of opponents at least as long as us, inversely
+// proportional to distance and ignored beyond AVOIDANCE_RADIUS
+fn avoidance_from_larger(pos: &Coord, board: &Board, you: &Snake, config: &Config) -> f64 {
+    let mut repulsion = 0.0;
+    for snake in nearest_opponents(board, you, config.modeled_opponents(), config) {
+        if snake.body.len() < you.body.len() {
+            continue;
+        }
+        let Some(head) = snake.body.first() else {
+            continue;
+        };
+        let dist = config.distance(pos, head, board);
+        if dist <= AVOIDANCE_RADIUS {
+            repulsion -= 1.0 / dist.max(1) as f64;
+        }
+    }
+    sanitize(repulsion)
+}
+
+fn evaluate_center_control(pos: &Coord, board: &Board) -> f64 {
+    let center_x = board.width as f64 / 2.0;
+    let center_y = board.height as f64 / 2.0;
+    let dist_from_center = ((pos.x as f64 - center_x).powi(2) + 
+                           (pos.y as f64 - center_y).powi(2)).sqrt();
+    
+    // Prefer positions closer to center
+    sanitize(25.0 - dist_from_center * 2.0)
+}
+
+// Entry point for choosing a move from a game state
+fn decide(state: &GameState, config: &Config, memory: &GameMemory) -> Direction {
+    explain_decision(state, config, memory).direction
+}
+
+// A chosen move, why it was chosen and the scores it was picked from
+struct Decision {
+    direction: Direction,
+    reason: &'static str,
+    scores: Vec<Move>,
+    // Food the move heads for, remembered to break ties the same way next turn
+    target: Option<Coord>,
+    // What the one-move evaluation alone would have played, when it ran
+    greedy: Option<Direction>,
+}
+
+impl Decision {
+    // A move taken without scoring the alternatives
+    fn forced(direction: Direction, reason: &'static str) -> Self {
+        Decision { direction, reason, scores: Vec::new(), target: None, greedy: None }
+    }
+
+    // Whether the search or the later adjustments changed the one-move evaluation's pick
+    fn overrides_greedy(&self) -> bool {
+        self.greedy.is_some_and(|greedy| greedy != self.direction)
+    }
+
+    // The same decision with its moves named the way the engine's origin names them
+    fn for_origin(mut self, origin: CoordOrigin) -> Self {
+        self.direction = origin.engine_direction(self.direction);
+        self.greedy = self.greedy.map(|greedy| origin.engine_direction(greedy));
+        for move_option in &mut self.scores {
+            move_option.direction = origin.engine_direction(move_option.direction);
+        }
+        self
+    }
+
+    // Whether the move came from a last-resort path rather than a finished evaluation
+    fn is_fallback(&self) -> bool {
+        matches!(self.reason, "panic fallback" | "search timeout" | "no safe move")
+    }
+}
+
+// Same as `decide`, keeping the reasoning for debug output
+fn explain_decision(state: &GameState, config: &Config, memory: &GameMemory) -> Decision {
+    // The time budget covers the whole decision, not just the search
+    let started = Instant::now();
+
+    // Turn-dependent logic (openings, decays) assumes a turn count from 0 up
+    let clamped = (state.turn < 0).then(|| {
+        warn!(turn = state.turn, "negative turn, treating it as turn 0");
+        GameState { turn: 0, ..state.clone() }
+    });
+    let state = clamped.as_ref().unwrap_or(state);
+    let preset = config.for_source(&state.game.source);
+    if preset.is_some() {
+        debug!(source = %state.game.source, "using the preset for this game source");
+    }
+    let config = preset.as_ref().unwrap_or(config);
+    let opening = config.for_turn(state.turn);
+    let config = opening.as_ref().unwrap_or(config);
+    let wrapped = config.for_ruleset(&state.game.ruleset);
+    let config = wrapped.as_ref().unwrap_or(config);
+    let stalemate = config.for_stalemate(memory);
+    if stalemate.is_some() {
+        debug!(stalled_turns = memory.stalled_turns, "circling without progress, playing for food and space");
+    }
+    let config = stalemate.as_ref().unwrap_or(config);
+    let deadline = started + move_budget(state, config);
+
+    // Opponents already at zero health are being removed by the engine this turn:
+    // their bodies neither block us nor threaten us
+    let dead = |snake: &Snake| snake.health <= 0 && snake.id != state.you.id;
+    let pruned = (config.ignore_dead_snakes && state.board.snakes.iter().any(dead)).then(|| {
+        let mut pruned = state.clone();
+        pruned.board.snakes.retain(|snake| !dead(snake));
+        pruned
+    });
+    let state = pruned.as_ref().unwrap_or(state);
+
+    // Malformed dimensions would break the flood fills and center math downstream
+    if state.board.width <= 0 || state.board.height <= 0 {
+        warn!(width = state.board.width, height = state.board.height, "degenerate board, answering with a default move");
+        return Decision::forced(Direction::Up, "degenerate board");
+    }
+
+    // A fixture with an empty snake list still has `you`: put it back on the board
+    // so its own body counts as blocked, and take any safe move
+    let you = &state.you;
+    if state.board.snakes.is_empty() && !you.body.is_empty() {
+        warn!("board lists no snakes, answering with a safe default move");
+        let mut board = state.board.clone();
+        board.snakes.push(you.clone());
+        let direction = safe_moves(you, &board).first().copied().unwrap_or(Direction::Up);
+        return Decision::forced(direction, "no snakes");
+    }
+
+    // The engine shouldn't ask a dead snake to move, but don't evaluate nonsense if it does
+    let on_board = state.board.snakes.iter().any(|snake| snake.id == you.id);
+    if you.health <= 0 || you.body.is_empty() || !on_board {
+        warn!(health = you.health, on_board, "our snake is already eliminated, answering with a default move");
+        return Decision::forced(Direction::Up, "eliminated");
+    }
+
+    // Forced move: nothing to weigh, skip the flood fills and the search
+    let safe = safe_moves(you, &state.board);
+    if let [only] = safe.as_slice() {
+        info!(r#move = %only, "only one safe move");
+        return Decision::forced(*only, "only safe move");
+    }
+
+    // Hard guardrail: never give up space below SPACE_FLOOR while some move keeps
+    // it, and when none does, just take the roomiest
+    if config.space_floor.is_some() {
+        match above_space_floor(&safe, you, &state.board, config).as_slice() {
+            [] => {
+                let roomiest = roomiest_move(&safe, you, &state.board, config).unwrap_or(Direction::Up);
+                info!(r#move = %roomiest, "every move drops below the space floor, taking the roomiest");
+                return Decision::forced(roomiest, "below space floor");
+            }
+            [only] => return Decision::forced(*only, "space floor"),
+            _ => {}
+        }
+    }
+
+    // Every opponent dies next turn whatever it does: don't gamble, just survive
+    if let Some(roomiest) = certain_win_move(&safe, you, &state.board, config) {
+        info!(r#move = %roomiest, "all opponents are trapped, the game is ours");
+        return Decision::forced(roomiest, "certain win");
+    }
+
+    // A fixed rule with fixed tie-breaks and no time-dependent search, so the
+    // same request always gets the same answer
+    if config.predictable {
+        let roomiest = roomiest_move(&safe, you, &state.board, config).unwrap_or(Direction::Up);
+        return Decision::forced(roomiest, "predictable");
+    }
+
+    // Chase our own tail, which always moves out of the way; give it up only
+    // when no move keeps it in reach
+    if config.safe_tail {
+        let head = &you.body[0];
+        let tail_safe: Vec<Direction> = safe
+            .iter()
+            .copied()
+            .filter(|dir| tail_reachable(&get_new_position(head, *dir), you, &state.board))
+            .collect();
+        let candidates = if tail_safe.is_empty() { &safe } else { &tail_safe };
+        let roomiest = roomiest_move(candidates, you, &state.board, config).unwrap_or(Direction::Up);
+        return Decision::forced(roomiest, "safe tail");
+    }
+
+    // Past the deadline the evaluation is skipped for the cheapest sound answer
+    if Instant::now() >= deadline {
+        warn!("out of time before the evaluation, taking the roomiest safe move");
+        return Decision::forced(roomiest_move(&safe, you, &state.board, config).unwrap_or(Direction::Up), "search timeout");
+    }
+    if config.maximin {
+        let chosen = maximin_move(&safe, you, &state.board, config).unwrap_or(Direction::Up);
+        return Decision::forced(chosen, "maximin");
+    }
+
+    // Alone on the board, or playing constrictor where every body only grows,
+    // a cycle through every cell keeps us alive for as long as it can be followed
+    let solo = state.board.snakes.len() == 1 || ["solo", "constrictor"].contains(&state.game.ruleset.name.as_str());
+    if config.fill_cycle && solo {
+        if let Some(step) = fill_cycle_step(&safe, you, &state.board, config) {
+            return Decision::forced(step, "fill cycle");
+        }
+    }
+
+    // Heads-up openings are a race for the center food and the space around it
+    let dueling = state.board.snakes.len() == 2;
+    if dueling && config.duel_opening_turns.is_some_and(|turns| state.turn < turns) {
+        if let Some(central) = duel_opening_move(&safe, you, &state.board, config) {
+            return Decision::forced(central, "duel opening");
+        }
+    }
+
+    // Walled off from every opponent, the game comes down to who outlasts whom
+    // in their own space: stop playing for position and just fill ours
+    if config.separated_endgame && state.board.snakes.len() > 1 {
+        let components = board_components(&state.board);
+        if components.separated(&you.id) {
+            debug!(space = components.space_for(&you.id), "separated from all opponents, filling our space");
+            let roomiest = roomiest_move(&safe, you, &state.board, config).unwrap_or(Direction::Up);
+            return Decision::forced(roomiest, "separated endgame");
+        }
+    }
+
+    bilinear_duel(state, config, memory, deadline)
+}
+
+// Time we may spend deciding: the engine's timeout less a margin for the network,
+// never more than HARD_MOVE_CAP_MS in case the timeout is missing or wrong
+fn move_budget(state: &GameState, config: &Config) -> Duration {
+    Duration::from_millis(state.game.timeout.saturating_sub(config.latency_margin_ms).min(config.hard_move_cap_ms))
+}
+
+// The move whose smallest reachable space over every combination of replies by
+// the nearest SEARCH_OPPONENTS opponents is largest; dying counts as no space
+fn maximin_move(moves: &[Direction], you: &Snake, board: &Board, config: &Config) -> Option<Direction> {
+    let opponent_ids: Vec<&str> = nearest_opponents(board, you, config.search_opponents.min(config.modeled_opponents()), config)
+        .into_iter()
+        .map(|snake| snake.id.as_str())
+        .collect();
+    let mut replies: Vec<Vec<Direction>> = vec![Vec::new()];
+    for _ in &opponent_ids {
+        replies = replies
+            .into_iter()
+            .flat_map(|reply| Direction::all().map(move |dir| [reply.as_slice(), &[dir]].concat()))
+            .collect();
+    }
+
+    let worst_space = |our_move: Direction| {
+        replies
+            .iter()
+            .map(|reply| {
+                let mut moves = vec![(you.id.as_str(), our_move)];
+                moves.extend(opponent_ids.iter().copied().zip(reply.iter().copied()));
+                let next = simulate_turn(board, &moves);
+                next.snakes
+                    .iter()
+                    .find(|snake| snake.id == you.id)
+                    .map_or(0, |snake| reachable_space(&next, &snake.body[0], config))
+            })
+            .min()
+            .unwrap_or(0)
+    };
+    // Scanned in reverse so that ties go to the earlier move
+    moves.iter().rev().copied().max_by_key(|dir| worst_space(*dir))
+}
+
+// Our roomiest safe move when at least one opponent remains and none has a safe move
+fn certain_win_move(safe: &[Direction], you: &Snake, board: &Board, config: &Config) -> Option<Direction> {
+    let mut opponents = board.snakes.iter().filter(|snake| snake.id != you.id).peekable();
+    opponents.peek()?;
+    if !opponents.all(|snake| safe_moves(snake, board).is_empty()) {
+        return None;
+    }
+    roomiest_move(safe, you, board, config)
+}
+
+// A cycle through the board, sweeping rows back and forth across every column
+// but the first and returning down that one. It covers every cell when a side
+// is even; odd boards, the standard sizes among them, have no such cycle, and
+// this one leaves out the corner (0, height - 1): the last row is picked up in
+// pairs of cells off the row below.
+fn fill_cycle(width: i32, height: i32) -> Option<Vec<Coord>> {
+    if width < 2 || height < 2 {
+        return None;
+    }
+    if height % 2 != 0 && width % 2 == 0 {
+        let transposed = fill_cycle(height, width)?;
+        return Some(transposed.into_iter().map(|cell| Coord { x: cell.y, y: cell.x }).collect());
+    }
+    let rows = height - height % 2;
+    let mut cycle = Vec::with_capacity((width * height) as usize);
+    for y in 0..rows {
+        if y % 2 == 0 {
+            cycle.extend((1..width).map(|x| Coord { x, y }));
+        } else if y + 1 == rows && rows < height {
+            for x in (2..width).rev().step_by(2) {
+                cycle.extend([Coord { x, y }, Coord { x, y: y + 1 }, Coord { x: x - 1, y: y + 1 }, Coord { x: x - 1, y }]);
+            }
+        } else {
+            cycle.extend((1..width).rev().map(|x| Coord { x, y }));
+        }
+    }
+    cycle.extend((0..rows).rev().map(|y| Coord { x: 0, y }));
+    Some(cycle)
+}
+
+// The move to the next cell of the fill cycle, as long as it is safe and leaves
+// us room; food growth or a detour can put us off the cycle, and then the
+// regular evaluation takes over
+fn fill_cycle_step(safe: &[Direction], you: &Snake, board: &Board, config: &Config) -> Option<Direction> {
+    let cycle = fill_cycle(board.width, board.height)?;
+    let head = you.body.first()?;
+    let index = cycle.iter().position(|cell| cell == head)?;
+    let next = &cycle[(index + 1) % cycle.len()];
+    let step = direction_between(head, next).filter(|dir| safe.contains(dir))?;
+    (!is_trap(next, board, you, 0, config)).then_some(step)
+}
+
+// The safe move closing in on the board center, where the standard setup puts
+// the contested food, that doesn't walk into a pocket too small for us. Ties go
+// to the cell with more ways out.
+fn duel_opening_move(moves: &[Direction], you: &Snake, board: &Board, config: &Config) -> Option<Direction> {
+    let head = you.body.first()?;
+    let center = Coord { x: board.width / 2, y: board.height / 2 };
+    moves
+        .iter()
+        .copied()
+        .filter(|dir| !is_trap(&get_new_position(head, *dir), board, you, 0, config))
+        .min_by_key(|dir| {
+            let pos = get_new_position(head, *dir);
+            (config.distance(&pos, &center, board), Reverse(safe_move_count(&pos, board)))
+        })
+}
+
+// The moves keeping at least SPACE_FLOOR cells reachable; all of them when unset
+fn above_space_floor(moves: &[Direction], you: &Snake, board: &Board, config: &Config) -> Vec<Direction> {
+    let (Some(floor), Some(head)) = (config.space_floor, you.body.first()) else {
+        return moves.to_vec();
+    };
+    moves
+        .iter()
+        .copied()
+        .filter(|dir| reachable_space(board, &get_new_position(head, *dir), config) >= floor)
+        .collect()
+}
+
+// The move with the most reachable space, ties going to the earlier move
+fn roomiest_move(moves: &[Direction], you: &Snake, board: &Board, config: &Config) -> Option<Direction> {
+    let head = you.body.first()?;
+    // Scanned in reverse so that max_by_key, which keeps the last maximum, keeps the first
+    moves
+        .iter()
+        .rev()
+        .copied()
+        .max_by_key(|dir| reachable_space(board, &get_new_position(head, *dir), config))
+}
+
+// Whether a cell is only safe under optimistic resolution: an opponent head can
+// reach it this turn, or it holds the tail of an opponent that may be about to eat
+fn risky_under_worst_resolution(pos: &Coord, board: &Board, you: &Snake) -> bool {
+    board.snakes.iter().filter(|snake| snake.id != you.id).any(|snake| {
+        let Some(head) = snake.body.first() else {
+            return false;
+        };
+        let contested = manhattan_distance(pos, head) == 1;
+        contested || (next_to_food(snake, board) && snake.body.last() == Some(pos))
+    })
+}
+
+// Whether the cell holds an opponent tail we can't be sure will move: at full
+// health the snake may have just eaten or just spawned, whatever its body shows
+fn ambiguous_tail(pos: &Coord, board: &Board, you: &Snake) -> bool {
+    board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id && snake.health >= 100)
+        .any(|snake| snake.body.last() == Some(pos) && tail_will_move(snake))
+}
+
+// Last-resort answer picked from the safe moves by the configured rule, else up
+fn fallback_move(state: &GameState, config: &Config) -> Direction {
+    let board = &state.board;
+    let safe = safe_moves(&state.you, board);
+    let Some(head) = state.you.body.first() else {
+        return Direction::Up;
+    };
+    let chosen = match config.fallback_strategy {
+        FallbackStrategy::FirstSafe => None,
+        FallbackStrategy::LargestSpace => roomiest_move(&safe, &state.you, board, config),
+        FallbackStrategy::Straight => heading(&state.you).filter(|dir| safe.contains(dir)),
+        // Scanned in reverse so that ties go to the earlier move
+        FallbackStrategy::Center => safe.iter().rev().copied().max_by(|a, b| {
+            let center = |dir: &Direction| evaluate_center_control(&get_new_position(head, *dir), board);
+            center(a).total_cmp(&center(b))
+        }),
+    };
+    chosen.or_else(|| safe.first().copied()).unwrap_or(Direction::Up)
+}
+
+// The turn's decision plus, with TRACK_PREDICTIONS, the opponent heads to check
+// next turn, both on the compute thread and inside the measured latency
+fn decide_turn(
+    state: &GameState,
+    config: &Config,
+    memory: &GameMemory,
+    detailed: bool,
+) -> (Decision, Option<HashMap<String, Coord>>) {
+    let decision = guarded_decision(state, config, memory, detailed);
+    let predictions = config.track_predictions.then(|| predict_opponent_heads(&state.board, &state.you, config));
+    (decision, predictions)
+}
+
+// `explain_decision`, except that a bug in the evaluation costs us at most this
+// turn rather than the worker. `detailed` turns the score breakdown logs on or off.
+fn guarded_decision(state: &GameState, config: &Config, memory: &GameMemory, detailed: bool) -> Decision {
+    guarded(state, config, detailed, || explain_decision(state, config, memory))
+}
+
+// Runs `decide`, answering with the fallback move if it panics
+fn guarded(state: &GameState, config: &Config, detailed: bool, decide: impl FnOnce() -> Decision) -> Decision {
+    DETAILED_LOGS.with(|flag| flag.set(detailed));
+    let outcome = panic::catch_unwind(AssertUnwindSafe(decide));
+    DETAILED_LOGS.with(|flag| flag.set(true));
+    match outcome {
+        Ok(decision) => decision,
+        Err(cause) => {
+            let message = cause
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| cause.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            error!(panic = %message, "decide panicked, falling back to a safe move");
+            Decision::forced(fallback_move(state, config), "panic fallback")
+        }
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    // Run before each move's evaluation, so tests can make it slow or make it panic
+    static EVALUATION_HOOK: Cell<Option<fn()>> = const { Cell::new(None) };
+}
+
+// Moves that neither reverse into the neck nor hit a wall or body
+fn safe_moves(snake: &Snake, board: &Board) -> Vec<Direction> {
+    let Some(head) = snake.body.first() else {
+        return Vec::new();
+    };
+    let reverse = reverse_direction(snake);
+    Direction::all()
+        .into_iter()
+        .filter(|dir| Some(*dir) != reverse && is_move_safe(&get_new_position(head, *dir), board))
+        .collect()
+}
+
+// Define strategy space for bilinear duel (simplified to 2D for movement directions)
+fn bilinear_duel(state: &GameState, config: &Config, memory: &GameMemory, deadline: Instant) -> Decision {
+    let you = &state.you;
+    let board = &state.board;
+
+    let possible_moves = Direction::all().into_iter().map(Move::new).collect();
+
+    // Score every move with the static evaluation, then back it up with the search
+    let terms = PositionTerms { deadline: Some(deadline), ..PositionTerms::new(state, config, memory) };
+    let mut moves = evaluate_moves(possible_moves, state, &terms);
+    if terms.timed_out.get() {
+        // Best of the moves evaluated in time, or the roomiest when none was
+        let evaluated = best_move(moves.clone());
+        let direction = if evaluated.score.is_finite() {
+            evaluated.direction
+        } else {
+            roomiest_move(&safe_moves(you, board), you, board, config).unwrap_or(Direction::Up)
+        };
+        warn!(r#move = %direction, "out of time during the one-move evaluation");
+        return Decision { direction, reason: "search timeout", scores: moves, target: None, greedy: None };
+    }
+    let greedy = best_move(moves.clone());
+    let mut reason = "highest score";
+    let alpha = config.search_blend;
+    if Instant::now() >= deadline {
+        warn!("out of time after the one-move evaluation, using its scores");
+        reason = "search timeout";
+    } else if alpha < 1.0 && config.search_depth > 0 {
+        let started = Instant::now();
+        let opponent_ids: Vec<&str> = nearest_opponents(board, you, config.search_opponents.min(config.modeled_opponents()), config)
+            .into_iter()
+            .map(|snake| snake.id.as_str())
+            .collect();
+
+        // Iterative deepening: keep the values of the deepest search that finished in time
+        let mut depth = initial_search_depth(board, config);
+        let mut deepest: Option<(u32, Vec<f64>)> = None;
+        let table = RefCell::new(HashMap::new());
+        loop {
+            let ctx = SearchContext {
+                you_id: &you.id,
+                opponent_ids: &opponent_ids,
+                config,
+                terms: &terms,
+                root_depth: depth,
+                deadline,
+                timed_out: Cell::new(false),
+                table: &table,
+            };
+            let values: Vec<f64> = moves
+                .iter()
+                .map(|m| if m.score.is_finite() { search_value(board, &ctx, m.direction, depth) } else { f64::NEG_INFINITY })
+                .collect();
+            if ctx.timed_out.get() {
+                break;
+            }
+            deepest = Some((depth, values));
+            if depth >= config.max_search_depth {
+                break;
+            }
+            depth += 1;
+        }
+
+        match deepest {
+            Some((depth, values)) => {
+                detail!(depth, elapsed_ms = started.elapsed().as_millis() as u64, "search finished");
+                for (move_option, deep) in moves.iter_mut().zip(values) {
+                    if !move_option.score.is_finite() {
+                        continue;
+                    }
+                    detail!(direction = %move_option.direction, static_score = move_option.score, minimax = deep, "search evaluated");
+                    move_option.score = sanitize(alpha * move_option.score + (1.0 - alpha) * deep);
+                }
+            }
+            None => {
+                warn!("search ran out of time before its first iteration, using static scores");
+                reason = "search timeout";
+            }
+        }
+    }
+    // Refinements on top of the scores, dropped when the time is already up
+    if Instant::now() < deadline {
+        prefer_larger_region(&mut moves, board, you, config);
+        reward_chokepoint(&mut moves, board, you, config);
+        reward_cutoff(&mut moves, board, you, config);
+        reward_corner_trap(&mut moves, board, you, config);
+        reward_pressure(&mut moves, board, you, config);
+        prefer_mirroring(&mut moves, board, you, config);
+    }
+    if moves.iter().all(|m| !m.score.is_finite()) {
+        reason = "no safe move";
+    }
+
+    let direction = keep_tail_in_reach(&moves, board, you, config)
+        .or_else(|| keep_to_target(&moves, memory, board, you))
+        .unwrap_or_else(|| best_move(moves.clone()).direction);
+    let target = evaluate_food(&you.body[0], board, you, &state.game.ruleset, config)
+        .filter(|(_, food_dir, _)| *food_dir == direction)
+        .map(|(_, _, food)| food);
+    if greedy.score.is_finite() && greedy.direction != direction {
+        let chosen_score = moves.iter().find(|m| m.direction == direction).map_or(f64::NAN, |m| m.score);
+        debug!(
+            greedy = %greedy.direction,
+            greedy_score = greedy.score,
+            chosen = %direction,
+            chosen_score,
+            "search overrode the greedy move"
+        );
+    }
+    Decision {
+        direction,
+        reason,
+        scores: moves,
+        target,
+        greedy: greedy.score.is_finite().then_some(greedy.direction),
+    }
+}
+
+// Among moves tied for the best score, one opening into a region that also
+// holds our tail, so we can keep looping behind ourselves once the board closes
+fn keep_tail_in_reach(moves: &[Move], board: &Board, you: &Snake, config: &Config) -> Option<Direction> {
+    let tail = you.body.last()?;
+    let tied = tied_for_best(moves);
+    if tied.len() < 2 {
+        return None;
+    }
+    let blocked = blocked_cells(board, config.model_opponent_growth);
+    let cap = config.flood_fill_cap(board);
+    let reaches: Vec<(Direction, bool)> = tied
+        .iter()
+        .map(|m| {
+            let region = flood_region(board, &get_new_position(&you.body[0], m.direction), &blocked, cap);
+            // The tail is blocked unless it moves away this turn, so touching it counts
+            let touches = region.contains(tail)
+                || Direction::all().into_iter().any(|dir| region.contains(&get_new_position(tail, dir)));
+            (m.direction, touches)
+        })
+        .collect();
+    if reaches.iter().all(|(_, touches)| *touches == reaches[0].1) {
+        return None;
+    }
+    reaches.into_iter().find(|(_, touches)| *touches).map(|(direction, _)| direction)
+}
+
+// Scores this close count as tied: mirror-image moves come out of the evaluation
+// and the search blend a rounding error apart rather than exactly equal
+const TIE_EPSILON: f64 = 1e-6;
+
+// The moves within TIE_EPSILON of the best score, none when every move is unsafe
+fn tied_for_best(moves: &[Move]) -> Vec<&Move> {
+    let best = moves.iter().map(|m| m.score).fold(f64::NEG_INFINITY, f64::max);
+    moves.iter().filter(|m| best.is_finite() && m.score >= best - TIE_EPSILON).collect()
+}
+
+// Among moves tied for the best score, the one closing in on the food we were
+// heading for last turn, so equal options don't make us flip-flop between goals
+fn keep_to_target(moves: &[Move], memory: &GameMemory, board: &Board, you: &Snake) -> Option<Direction> {
+    let target = memory.food_target.as_ref().filter(|target| board.food.contains(target))?;
+    let tied = tied_for_best(moves);
+    if tied.len() < 2 {
+        return None;
+    }
+    tied.into_iter()
+        .min_by_key(|m| manhattan_distance(&get_new_position(&you.body[0], m.direction), target))
+        .map(|m| m.direction)
+}
+
+// Starting depth for iterative deepening: deeper on sparse boards, where few
+// snakes leave the branching small, shallower on crowded ones
+fn initial_search_depth(board: &Board, config: &Config) -> u32 {
+    let area = (board.width * board.height).max(1) as f64;
+    let occupied: usize = board.snakes.iter().map(|snake| snake.body.len()).sum();
+    let fill = occupied as f64 / area;
+
+    let depth = if board.snakes.len() <= 2 && fill < 0.25 {
+        config.search_depth + 1
+    } else if board.snakes.len() >= 4 || fill > 0.5 {
+        config.search_depth.saturating_sub(1)
+    } else {
+        config.search_depth
+    };
+    depth.clamp(1, config.max_search_depth.max(1))
+}
+
+// When the candidate moves lead into separate regions (e.g. threading past an
+// opponent), strongly prefer the ones opening into the largest region
+fn prefer_larger_region(moves: &mut [Move], board: &Board, you: &Snake, config: &Config) {
+    let blocked = blocked_cells(board, config.model_opponent_growth);
+    let cap = config.flood_fill_cap(board);
+    let regions: Vec<(usize, Coord, HashSet<Coord>)> = moves
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.score.is_finite())
+        .map(|(index, m)| {
+            let pos = get_new_position(&you.body[0], m.direction);
+            let region = flood_region(board, &pos, &blocked, cap);
+            (index, pos, region)
+        })
+        .collect();
+
+    let split = regions
+        .iter()
+        .any(|(_, _, region)| regions.iter().any(|(_, pos, _)| !region.contains(pos)));
+    if !split {
+        return;
+    }
+    let largest = regions.iter().map(|(_, _, region)| region.len()).max().unwrap_or(0);
+    for (index, _, region) in &regions {
+        if region.len() == largest {
+            moves[*index].score = sanitize(moves[*index].score + config.weights.larger_region);
+        }
+    }
+}
+
+// Open orthogonal neighbours of a cell: in bounds and not blocked
+fn open_neighbours(cell: &Coord, board: &Board, blocked: &HashSet<Coord>) -> Vec<Coord> {
+    Direction::all()
+        .into_iter()
+        .map(|dir| get_new_position(cell, dir))
+        .filter(|next| next.x >= 0 && next.x < board.width && next.y >= 0 && next.y < board.height)
+        .filter(|next| !blocked.contains(next))
+        .collect()
+}
+
+// Rewards passing through a chokepoint, a cell with exactly two open neighbours
+// one of which is our head: our body then plugs the passage behind us, so it
+// pays when the side ahead is the larger one and every opponent is left behind
+fn reward_chokepoint(moves: &mut [Move], board: &Board, you: &Snake, config: &Config) {
+    let head = &you.body[0];
+    let mut blocked = blocked_cells(board, config.model_opponent_growth);
+    blocked.remove(head);
+    let cap = config.flood_fill_cap(board);
+    let area = (board.width * board.height).max(1) as f64;
+    let opponent_heads: Vec<&Coord> = board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id)
+        .filter_map(|snake| snake.body.first())
+        .collect();
+    if opponent_heads.is_empty() {
+        return;
+    }
+
+    for move_option in moves.iter_mut().filter(|m| m.score.is_finite()) {
+        let pos = get_new_position(head, move_option.direction);
+        let neighbours = open_neighbours(&pos, board, &blocked);
+        if neighbours.len() != 2 || !neighbours.contains(head) {
+            continue;
+        }
+        let Some(ahead) = neighbours.iter().find(|cell| *cell != head) else {
+            continue;
+        };
+        let mut sealed = blocked.clone();
+        sealed.insert(pos.clone());
+        let ahead_side = flood_region(board, ahead, &sealed, cap);
+        if ahead_side.contains(head) {
+            continue;
+        }
+        let behind_side = flood_region(board, head, &sealed, cap);
+
+        // A head next to a side can enter it next turn
+        let touches = |side: &HashSet<Coord>, cell: &Coord| {
+            Direction::all().into_iter().any(|dir| side.contains(&get_new_position(cell, dir)))
+        };
+        let left_behind = opponent_heads
+            .iter()
+            .all(|opponent| !touches(&ahead_side, opponent) && touches(&behind_side, opponent));
+        if !left_behind || ahead_side.len() <= behind_side.len() {
+            continue;
+        }
+        let margin = (ahead_side.len() - behind_side.len()) as f64 / area;
+        move_option.score = sanitize(move_option.score + margin * config.weights.chokepoint);
+        detail!(direction = %move_option.direction, ahead = ahead_side.len(), behind = behind_side.len(), "chokepoint taken");
+    }
+}
+
+// Highest scoring move, defaulting to up when there is nothing to choose from
+fn best_move(mut moves: Vec<Move>) -> Move {
+    moves.sort_by(|a, b| b.score.total_cmp(&a.score));
+    moves.into_iter().next().unwrap_or(Move::new(Direction::Up))
+}
+
+fn evaluate_moves(mut moves: Vec<Move>, state: &GameState, terms: &PositionTerms) -> Vec<Move> {
+    let (config, hunger) = (terms.config, terms.hunger);
+    let you = &state.you;
+    let head = &you.body[0];
+    let board = &state.board;
+    let reverse = reverse_direction(you);
+    let cautious_moves: Vec<Direction> = if config.assume_worst_resolution || config.conservative_tails {
+        safe_moves(you, board)
+            .into_iter()
+            .filter(|dir| {
+                let pos = get_new_position(head, *dir);
+                let risky = config.assume_worst_resolution && risky_under_worst_resolution(&pos, board, you);
+                let ambiguous = config.conservative_tails && ambiguous_tail(&pos, board, you);
+                !risky && !ambiguous
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let roomy_moves = above_space_floor(&safe_moves(you, board), you, board, config);
+    if hunger == Hunger::Desperate {
+        warn!(health = you.health, "no food reachable before starving, taking risks to eat");
+    }
+
+    for move_option in &mut moves {
+        let new_pos = get_new_position(head, move_option.direction);
+
+        // Immediate death check
+        let too_risky = (!cautious_moves.is_empty() && !cautious_moves.contains(&move_option.direction))
+            || (!roomy_moves.is_empty() && !roomy_moves.contains(&move_option.direction));
+        if Some(move_option.direction) == reverse || !is_move_safe(&new_pos, board) || too_risky {
+            move_option.score = f64::NEG_INFINITY;
+            continue;
+        }
+        // Out of time: moves not evaluated yet are left out rather than guessed at
+        if terms.timed_out.get() || terms.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            terms.timed_out.set(true);
+            move_option.score = f64::NEG_INFINITY;
+            continue;
+        }
+        #[cfg(test)]
+        if let Some(hook) = EVALUATION_HOOK.with(Cell::get) {
+            hook();
+        }
+
+        // Everything the search also scores at its leaves
+        let (after, moved) = step_onto(&new_pos, you, board);
+        let (mut score, available_space) = position_score(&after, &moved, terms);
+        score += step_score(&new_pos, board, terms);
+
+        move_option.score = score;
+        detail!(direction = %move_option.direction, score, space = available_space, "move evaluated");
+    }
+
+    moves
+}
+
+// Rewards moves by how many safe moves they leave the cutoff target, the way
+// forward along its wall counting double: blocking it while the wall closes the
+// other side drives the snake into the corner ahead
+fn reward_corner_trap(moves: &mut [Move], board: &Board, you: &Snake, config: &Config) {
+    let Some(target) = cutoff_target(board, you, config) else {
+        return;
+    };
+    let Some(forward) = heading(target) else {
+        return;
+    };
+    let exits_before = safe_moves(target, board);
+    for move_option in moves.iter_mut().filter(|m| m.score.is_finite()) {
+        let after = simulate_turn(board, &[(you.id.as_str(), move_option.direction)]);
+        let Some(pinned) = after.snakes.iter().find(|snake| snake.id == target.id) else {
+            continue;
+        };
+        let exits_after = safe_moves(pinned, &after);
+        let cut = exits_before.iter().filter(|dir| !exits_after.contains(dir)).count();
+        let forward_cut = exits_before.contains(&forward) && !exits_after.contains(&forward);
+        let reward = (cut + forward_cut as usize) as f64 * config.weights.corner_trap;
+        move_option.score = sanitize(move_option.score + reward);
+        detail!(direction = %move_option.direction, cut, forward_cut, "corner trap evaluated");
+    }
+}
+
+// In aggressive mode, the opponent most worth squeezing: the nearest one longer
+// than us, or failing that the longest on the board
+fn dangerous_opponent<'a>(board: &'a Board, you: &Snake, config: &Config) -> Option<&'a Snake> {
+    if config.personality != Personality::Aggressive {
+        return None;
+    }
+    let head = you.body.first()?;
+    let opponents = || board.snakes.iter().filter(|snake| snake.id != you.id && !snake.body.is_empty());
+    opponents()
+        .filter(|snake| snake.body.len() > you.body.len())
+        .min_by_key(|snake| config.distance(head, &snake.body[0], board))
+        .or_else(|| opponents().max_by_key(|snake| snake.body.len()))
+}
+
+// Rewards moves by how much they shrink the most dangerous opponent's reachable space
+fn reward_pressure(moves: &mut [Move], board: &Board, you: &Snake, config: &Config) {
+    if let Some(target) = dangerous_opponent(board, you, config) {
+        reward_space_reduction(moves, board, you, target, config.weights.pressure, config);
+    }
+}
+
+// Adds `weight` per cell each move takes away from `target`'s reachable space
+fn reward_space_reduction(moves: &mut [Move], board: &Board, you: &Snake, target: &Snake, weight: f64, config: &Config) {
+    let space_before = reachable_space(board, &target.body[0], config);
+    for move_option in moves.iter_mut().filter(|m| m.score.is_finite()) {
+        let after = simulate_turn(board, &[(you.id.as_str(), move_option.direction)]);
+        let space_after = reachable_space(&after, &target.body[0], config);
+        let reduction = (space_before - space_after).max(0);
+        move_option.score = sanitize(move_option.score + reduction as f64 * weight);
+        detail!(direction = %move_option.direction, target = %target.id, reduction, "space reduction evaluated");
+    }
+}
+
+// In aggressive mode, the nearest shorter opponent whose head runs along a wall:
+// our body between it and the open board pins it against the edge
+fn cutoff_target<'a>(board: &'a Board, you: &Snake, config: &Config) -> Option<&'a Snake> {
+    if config.personality != Personality::Aggressive {
+        return None;
+    }
+    let head = you.body.first()?;
+    board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id && !snake.body.is_empty() && config.wins_head_on(you, snake, board))
+        .filter(|snake| {
+            let target = &snake.body[0];
+            target.x <= 0 || target.y <= 0 || target.x >= board.width - 1 || target.y >= board.height - 1
+        })
+        .min_by_key(|snake| manhattan_distance(head, &snake.body[0]))
+}
+
+// Rewards moves by how much they shrink the cutoff target's reachable space
+fn reward_cutoff(moves: &mut [Move], board: &Board, you: &Snake, config: &Config) {
+    if let Some(target) = cutoff_target(board, you, config) {
+        reward_space_reduction(moves, board, you, target, config.weights.cutoff, config);
+    }
+}
+
+// In MIRROR_OPPONENT mode, rewards the move that keeps our head at the same
+// offset from the opponent's, assuming it carries on in its current heading
+fn prefer_mirroring(moves: &mut [Move], board: &Board, you: &Snake, config: &Config) {
+    if !config.mirror_opponent || board.snakes.len() != 2 {
+        return;
+    }
+    let Some(opponent) = board.snakes.iter().find(|snake| snake.id != you.id && !snake.body.is_empty()) else {
+        return;
+    };
+    let head = &you.body[0];
+    let their_head = &opponent.body[0];
+    if space_differential(board, head, their_head) <= 0 {
+        return;
+    }
+
+    let predicted = heading(opponent).map_or(their_head.clone(), |dir| get_new_position(their_head, dir));
+    let offset = (head.x - their_head.x, head.y - their_head.y);
+    for move_option in moves.iter_mut().filter(|m| m.score.is_finite()) {
+        let pos = get_new_position(head, move_option.direction);
+        if (pos.x - predicted.x, pos.y - predicted.y) == offset {
+            move_option.score = sanitize(move_option.score + config.weights.mirror);
+            detail!(direction = %move_option.direction, "mirrors the opponent");
+        }
+    }
+}
+
+const WIN_SCORE: f64 = 100_000.0;
+const LOSS_SCORE: f64 = -100_000.0;
+const ELIMINATION_BONUS: f64 = 1_000.0;
+
+// Up to `count` opponents, nearest head first
+fn nearest_opponents<'a>(board: &'a Board, you: &Snake, count: usize, config: &Config) -> Vec<&'a Snake> {
+    let Some(head) = you.body.first() else {
+        return Vec::new();
+    };
+    let mut opponents: Vec<&Snake> = board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id && !snake.body.is_empty())
+        .collect();
+    opponents.sort_by_key(|snake| config.distance(head, &snake.body[0], board));
+    opponents.truncate(count);
+    opponents
+}
+
+// Advances the board by one turn. Snakes listed in `moves` move in the given
+// direction, everyone else stays put. Applies feeding, starvation and collisions.
+// A snake that eats is back at full health and stacks a segment on its tail, so
+// later plies see that tail stay put for a turn.
+fn simulate_turn(board: &Board, moves: &[(&str, Direction)]) -> Board {
+    let mut next = board.clone();
+    let mut eaten = Vec::new();
+    // Turning back into the neck is never legal, even at length 2 where the
+    // neck is also the tail that moves out of the way
+    let mut reversed = Vec::new();
+
+    for snake in &mut next.snakes {
+        let Some((_, direction)) = moves.iter().find(|(id, _)| *id == snake.id) else {
+            continue;
+        };
+        if snake.body.is_empty() {
+            continue;
+        }
+        if reverse_direction(snake) == Some(*direction) {
+            reversed.push(snake.id.clone());
+        }
+        let new_head = get_new_position(&snake.body[0], *direction);
+        snake.body.insert(0, new_head.clone());
+        snake.body.pop();
+        snake.health -= 1;
+
+        // Food is only removed once everyone has moved: two heads arriving together both eat
+        if next.food.contains(&new_head) {
+            eaten.push(new_head);
+            snake.health = 100;
+            let tail = snake.body[snake.body.len() - 1].clone();
+            snake.body.push(tail);
+        }
+    }
+    next.food.retain(|food| !eaten.contains(food));
+
+    let eliminated: Vec<String> = next
+        .snakes
+        .iter()
+        .filter(|snake| {
+            let Some(head) = snake.body.first() else {
+                return true;
+            };
+            let out_of_bounds = head.x < 0 || head.x >= next.width || head.y < 0 || head.y >= next.height;
+            // Segments still stacked under a head that hasn't moved since spawning aren't a collision
+            let hit_body = next.snakes.iter().any(|other| {
+                other
+                    .body
+                    .iter()
+                    .skip(1)
+                    .skip_while(|segment| other.id == snake.id && *segment == head)
+                    .any(|segment| segment == head)
+            });
+            let lost_head_on = next.snakes.iter().any(|other| {
+                other.id != snake.id && other.body.first() == Some(head) && other.body.len() >= snake.body.len()
+            });
+            out_of_bounds || snake.health <= 0 || hit_body || lost_head_on || reversed.contains(&snake.id)
+        })
+        .map(|snake| snake.id.clone())
+        .collect();
+
+    next.snakes.retain(|snake| !eliminated.contains(&snake.id));
+    next
+}
+
+// The board and our snake after moving our head to `new_pos`, everyone else staying put
+fn step_onto(new_pos: &Coord, you: &Snake, board: &Board) -> (Board, Snake) {
+    let mut moved = you.clone();
+    moved.body.insert(0, new_pos.clone());
+    if !board.food.contains(new_pos) {
+        moved.body.pop();
+    }
+
+    let mut after = board.clone();
+    after.food.retain(|food| food != new_pos);
+    after.snakes.retain(|snake| snake.id != you.id);
+    after.snakes.push(moved.clone());
+    (after, moved)
+}
+
+// Whether our tail is still reachable after moving the head to `new_pos`
+fn tail_reachable(new_pos: &Coord, you: &Snake, board: &Board) -> bool {
+    let (after, moved) = step_onto(new_pos, you, board);
+    can_reach_own_tail(&after, &moved)
+}
+
+// What the positional terms need to know about the decision being made
+struct PositionTerms<'a> {
+    config: &'a Config,
+    ruleset: &'a Ruleset,
+    memory: &'a GameMemory,
+    turn: i32,
+    // How badly we need food at the root of the decision
+    hunger: Hunger,
+    food: Option<FoodPlan>,
+    // When the per-move evaluation has to stop, and whether it did
+    deadline: Option<Instant>,
+    timed_out: Cell<bool>,
+    // Our space shrank sharply since last turn, so room to escape counts extra
+    boxed_in: bool,
+    // Food is below the ruleset minimum, so more will spawn soon
+    awaiting_spawns: bool,
+}
+
+// The food the decision is heading for, picked once at the root
+struct FoodPlan {
+    target: Coord,
+    // Cost of the cheapest path to the target from the root position
+    cost: i32,
+    // What closing in on it is worth
+    score: f64,
+}
+
+impl<'a> PositionTerms<'a> {
+    fn new(state: &'a GameState, config: &'a Config, memory: &'a GameMemory) -> Self {
+        let you = &state.you;
+        let current_space = reachable_space(&state.board, &you.body[0], config);
+        let boxed_in = memory.boxed_in(current_space, config.box_in_shrink);
+        if boxed_in {
+            info!(space = current_space, last_space = memory.last_space, "space is closing in, looking for a way out");
+        }
+        let hunger = hunger(&you.body[0], you, &state.board, &state.game.ruleset, config);
+        PositionTerms {
+            config,
+            ruleset: &state.game.ruleset,
+            memory,
+            turn: state.turn,
+            hunger,
+            food: food_plan(state, config, hunger),
+            deadline: None,
+            timed_out: Cell::new(false),
+            boxed_in,
+            awaiting_spawns: config.anticipate_food_spawns
+                && (state.board.food.len() as i32) < state.game.ruleset.settings.minimum_food,
+        }
+    }
+}
+
+// The most desirable food, or when hungry the first stop of the planned route,
+// scored by how badly we need it
+fn food_plan(state: &GameState, config: &Config, hunger: Hunger) -> Option<FoodPlan> {
+    let (you, board, ruleset) = (&state.you, &state.board, &state.game.ruleset);
+    let (food_dist, _, nearest) = evaluate_food(&you.body[0], board, you, ruleset, config)?;
+    let target = if config.plan_food_route && hunger == Hunger::Urgent {
+        route_target(board, you, ruleset, config).unwrap_or(nearest)
+    } else {
+        nearest
+    };
+    let cost = food_path_cost(&you.body[0], &target, board, ruleset, config)?;
+    let food_score = match hunger {
+        Hunger::Fed => calculate_food_score(food_dist, you.health),
+        // Score as if health were critical
+        Hunger::Urgent => calculate_food_score(food_dist, you.health.min(24)),
+        // Outweighs threat penalties: a risky meal beats certain starvation
+        Hunger::Desperate => calculate_food_score(food_dist, 0) + 1000.0,
+    };
+    Some(FoodPlan { target, cost, score: food_score * config.food_weight() * length_food_drive(board, you) })
+}
+
+// The first food the planned collection route reaches
+fn route_target(board: &Board, you: &Snake, ruleset: &Ruleset, config: &Config) -> Option<Coord> {
+    let mut at = you.body.first()?.clone();
+    for dir in plan_food_route(board, you, ruleset, config) {
+        at = neighbour(&at, dir, board, config.toroidal);
+        if board.food.contains(&at) {
+            return Some(at);
+        }
+    }
+    None
+}
+
+// Health it costs to walk the cheapest safe path from `from` to `to`
+fn food_path_cost(from: &Coord, to: &Coord, board: &Board, ruleset: &Ruleset, config: &Config) -> Option<i32> {
+    let step_cost = hazard_step_cost(board, ruleset);
+    a_star(board, from, to, config.toroidal, &step_cost).map(|path| path.iter().map(&step_cost).sum())
+}
+
+// The planned food's score for a position that got closer to it than the root
+// was, or whose head already ate it; nothing otherwise
+fn food_term(board: &Board, you: &Snake, terms: &PositionTerms) -> f64 {
+    let Some(plan) = &terms.food else {
+        return 0.0;
+    };
+    let (config, hunger, head) = (terms.config, terms.hunger, &you.body[0]);
+    let uneaten = board.food.contains(&plan.target);
+    let closer = if uneaten {
+        food_path_cost(head, &plan.target, board, terms.ruleset, config).is_some_and(|cost| cost < plan.cost)
+    } else {
+        // Gone: either we ate it and are still lying over the cell, or someone else did
+        you.body.contains(&plan.target)
+    };
+    if !closer {
+        return 0.0;
+    }
+
+    // In a duel, don't trade away the space lead for a meal we can do without
+    let gives_up_space = hunger == Hunger::Fed
+        && duel_food_opponent(board, you, config).is_some_and(|opp| space_differential(board, head, &opp.body[0]) < 0);
+    // Don't let food lure us into a pocket we can't fit in once we've grown
+    let traps_us = hunger != Hunger::Desperate && is_trap(head, board, you, usize::from(uneaten), config);
+    // Nor walk us into a head-to-head we would lose
+    let lures_us = hunger == Hunger::Fed
+        && you.body.get(1).is_some_and(|from| nears_larger_opponent(head, from, board, you, config));
+    if gives_up_space || traps_us || lures_us {
+        0.0
+    } else {
+        plan.score
+    }
+}
+
+// Positional terms of a board where our head has just arrived, scored the same
+// way by the one-move evaluation and by the search at its leaves, so the two
+// can't drift apart. Also returns the size of the region we are in.
+fn position_score(board: &Board, you: &Snake, terms: &PositionTerms) -> (f64, i32) {
+    let config = terms.config;
+    let head = &you.body[0];
+
+    // Every term is sanitized on its own, so one that goes non-finite drops out
+    // instead of zeroing the whole score
+
+    // Space evaluation (weighted highest)
+    let (space, usable_space, region_food) = estimate_space(board, head, config);
+    let mut score = sanitize(usable_space * config.weights.space);
+    score += sanitize(region_food as f64 * config.weights.region_food);
+    score += sanitize(config.space_floor_penalty(space, you));
+    if terms.boxed_in {
+        let area = (board.width * board.height).max(1) as f64;
+        score += sanitize(space as f64 / area * config.weights.escape);
+    }
+    if !can_reach_own_tail(board, you) {
+        score -= sanitize(config.weights.tail_access);
+    }
+    score += sanitize(future_flexibility(board, you, FLEXIBILITY_DEPTH) * config.weights.flexibility);
+    score += sanitize(food_term(board, you, terms));
+    score += sanitize(food_access(head, board, you, config));
+    score += sanitize(edge_crowding(head, board, you, config));
+    score += sanitize(hazard_quadrant_penalty(head, board, terms.ruleset, config));
+    score += sanitize(body_spread(you) * config.weights.spine);
+    score += sanitize(food_denial_score(board, you, config));
+
+    // Threat evaluation
+    let threat = evaluate_threats(head, board, you, config);
+    score += sanitize(threat * config.weights.threat * config.threat_scale());
+    score += sanitize(avoidance_from_larger(head, board, you, config) * config.weights.avoid_larger * config.threat_scale());
+
+    // Center control evaluation
+    score += sanitize(evaluate_center_control(head, board) * config.center_weight(terms.turn));
+    if terms.awaiting_spawns {
+        score += sanitize(evaluate_center_control(head, board) * config.weights.spawn_center);
+    }
+
+    // Loop avoidance: with nothing pressing, don't pace back over recent cells
+    let calm = terms.hunger == Hunger::Fed && you.health >= 50 && threat == 0.0;
+    if calm && terms.memory.visited_recently(head) {
+        score -= sanitize(config.weights.loop_penalty);
+    }
+    (sanitize(score), space)
+}
+
+// Everything the search needs besides the board it is looking at
+struct SearchContext<'a> {
+    you_id: &'a str,
+    // Opponents the search moves, nearest first
+    opponent_ids: &'a [&'a str],
+    config: &'a Config,
+    terms: &'a PositionTerms<'a>,
+    // Depth the current iteration started from
+    root_depth: u32,
+    // Once passed, the search unwinds and the iteration is discarded
+    deadline: Instant,
+    timed_out: Cell<bool>,
+    // Positions already searched during this request; shared across iterations
+    table: &'a RefCell<HashMap<u64, TableEntry>>,
+}
+
+// Cached result of searching a position with `depth` of our moves remaining
+#[derive(Clone, Copy)]
+struct TableEntry {
+    depth: u32,
+    value: f64,
+    best: Direction,
+}
+
+// splitmix64 finalizer, used to derive the Zobrist keys
+fn mix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+// Zobrist-style hash of a search position: a key per (cell, piece) XORed together,
+// plus each snake's health and length and the parity of the ply
+fn position_hash(board: &Board, ply: u32) -> u64 {
+    let cell_key = |coord: &Coord, piece: u64| {
+        let cell = (coord.y as u64).wrapping_mul(board.width as u64).wrapping_add(coord.x as u64);
+        mix64(cell.wrapping_mul(0x1_0000).wrapping_add(piece))
+    };
+
+    let mut hash = mix64(ply as u64 % 2);
+    for food in &board.food {
+        hash ^= cell_key(food, 0);
+    }
+    for snake in &board.snakes {
+        // FNV-1a of the id, so the keys don't depend on the order of `snakes`
+        let salt = snake.id.bytes().fold(0xCBF2_9CE4_8422_2325_u64, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01B3));
+        for (index, segment) in snake.body.iter().enumerate() {
+            let role = match index {
+                0 => 1,
+                i if i + 1 == snake.body.len() => 2,
+                _ => 3,
+            };
+            hash ^= cell_key(segment, salt.wrapping_add(role));
+        }
+        hash ^= mix64(salt ^ ((snake.health as u64) << 32 | snake.body.len() as u64));
+    }
+    hash
+}
+
+// Terms for the cell our head moves onto from `board`. Unlike position_score
+// they are paid for every move, so the search adds them up along a line rather
+// than only looking at where it ends.
+fn step_score(pos: &Coord, board: &Board, terms: &PositionTerms) -> f64 {
+    sanitize(hazard_proximity_penalty(pos, board, terms.ruleset, terms.config))
+}
+
+// Static evaluation of a position from our point of view
+// `opponent_ids` are the modeled opponents that were alive before the last move
+fn evaluate_position(board: &Board, opponent_ids: &[&str], ctx: &SearchContext) -> f64 {
+    let config = ctx.config;
+    let Some(you) = board.snakes.iter().find(|snake| snake.id == ctx.you_id) else {
+        return LOSS_SCORE;
+    };
+    let opponents: Vec<&Snake> = opponent_ids
+        .iter()
+        .filter_map(|id| board.snakes.iter().find(|snake| snake.id == *id))
+        .collect();
+    if !opponent_ids.is_empty() && opponents.is_empty() {
+        return WIN_SCORE;
+    }
+
+    let (mut score, space) = position_score(board, you, ctx.terms);
+    score += you.health as f64 * 0.1;
+
+    // Taking out one of several modeled opponents is good, but not yet a win
+    score += (opponent_ids.len() - opponents.len()) as f64 * ELIMINATION_BONUS;
+    for opponent in opponents {
+        score += (you.body.len() as f64 - opponent.body.len() as f64) * 10.0;
+
+        // Commit to the kill: close in on a shorter opponent's head, but only while
+        // we keep enough room behind us to escape if the attack fails
+        let can_win_head_on = config.wins_head_on(you, opponent, board);
+        if config.personality == Personality::Aggressive && can_win_head_on && space as usize > you.body.len() {
+            let distance = config.distance(&you.body[0], &opponent.body[0], board);
+            score -= distance as f64 * 15.0;
+        }
+    }
+    sanitize(score)
+}
+
+// Where our opponent model expects each opponent's head to be next turn
+fn predict_opponent_heads(board: &Board, you: &Snake, config: &Config) -> HashMap<String, Coord> {
+    board
+        .snakes
+        .iter()
+        .filter(|snake| snake.id != you.id)
+        .filter_map(|snake| {
+            let head = snake.body.first()?;
+            let predicted = get_new_position(head, greedy_reply(board, &snake.id, config));
+            Some((snake.id.clone(), predicted))
+        })
+        .collect()
+}
+
+// Cheap opponent model: the safe move with the most room, or up if it is stuck
+fn greedy_reply(board: &Board, snake_id: &str, config: &Config) -> Direction {
+    let Some(snake) = board.snakes.iter().find(|snake| snake.id == snake_id) else {
+        return Direction::Up;
+    };
+    let Some(head) = snake.body.first() else {
+        return Direction::Up;
+    };
+
+    let mut best = (Direction::Up, -1);
+    for dir in safe_moves(snake, board) {
+        let next = get_new_position(head, dir);
+        let space = reachable_space(board, &next, config);
+        if space > best.1 {
+            best = (dir, space);
+        }
+    }
+    best.0
+}
+
+// Every combination of the `alive` opponents' moves at `ply`, or their greedy
+// replies past OPPONENT_DEPTH
+fn opponent_replies(board: &Board, alive: &[&str], ply: u32, config: &Config) -> Vec<Vec<Direction>> {
+    let mut replies: Vec<Vec<Direction>> = vec![Vec::new()];
+    for id in alive {
+        let options = if ply <= config.opponent_depth() {
+            Direction::all().to_vec()
+        } else {
+            vec![greedy_reply(board, id, config)]
+        };
+        replies = replies
+            .into_iter()
+            .flat_map(|reply| options.iter().map(move |dir| [reply.as_slice(), &[*dir]].concat()))
+            .collect();
+    }
+    replies
+}
+
+// Minimax value of playing `our_move` now: the opponent answers with its most
+// damaging reply, after which we pick our best continuation for `depth - 1` more moves
+fn search_value(board: &Board, ctx: &SearchContext, our_move: Direction, depth: u32) -> f64 {
+    let (you_id, config) = (ctx.you_id, ctx.config);
+    if ctx.timed_out.get() || Instant::now() >= ctx.deadline {
+        ctx.timed_out.set(true);
+        return 0.0;
+    }
+    let alive: Vec<&str> = ctx
+        .opponent_ids
+        .iter()
+        .copied()
+        .filter(|id| board.snakes.iter().any(|snake| snake.id == *id))
+        .collect();
+    let ply = ctx.root_depth.saturating_sub(depth) + 1;
+    let step = board
+        .snakes
+        .iter()
+        .find(|snake| snake.id == you_id)
+        .and_then(|you| you.body.first())
+        .map_or(0.0, |head| step_score(&get_new_position(head, our_move), board, ctx.terms));
+    let replies = opponent_replies(board, &alive, ply, config);
+
+    let mut worst = f64::INFINITY;
+    for reply in replies {
+        // Leaf evaluations are the expensive part, so don't start one past the deadline
+        if Instant::now() >= ctx.deadline {
+            ctx.timed_out.set(true);
+            return 0.0;
+        }
+        let mut moves = vec![(you_id, our_move)];
+        moves.extend(alive.iter().copied().zip(reply));
+        let next = simulate_turn(board, &moves);
+
+        let still_alive = next.snakes.iter().any(|snake| snake.id == you_id);
+        let opponents_died = !alive.is_empty() && alive.iter().all(|id| !next.snakes.iter().any(|snake| snake.id == *id));
+        let value = if depth <= 1 || !still_alive || opponents_died {
+            evaluate_position(&next, &alive, ctx)
+        } else {
+            best_reply_value(&next, ctx, ply + 1, depth - 1)
+        };
+        worst = worst.min(step + value);
+    }
+
+    worst
+}
+
+// Value of our best move in `board` with `depth` moves left, reusing a cached
+// result searched at least as deep
+fn best_reply_value(board: &Board, ctx: &SearchContext, ply: u32, depth: u32) -> f64 {
+    let key = position_hash(board, ply);
+    if let Some(entry) = ctx.table.borrow().get(&key).filter(|entry| entry.depth >= depth) {
+        detail!(depth = entry.depth, best = %entry.best, "transposition hit");
+        return entry.value;
+    }
+
+    let mut best = (Direction::Up, f64::NEG_INFINITY);
+    for dir in Direction::all() {
+        let value = search_value(board, ctx, dir, depth);
+        if value > best.1 {
+            best = (dir, value);
+        }
+    }
+    // An aborted subtree returns placeholder values that must not be reused
+    if !ctx.timed_out.get() {
+        ctx.table.borrow_mut().insert(key, TableEntry { depth, value: best.1, best: best.0 });
+    }
+    best.1
+}
+
+// Whether a path leads from the snake's head to its own tail, the one cell
+// that is guaranteed to keep opening up as we move
+fn can_reach_own_tail(board: &Board, snake: &Snake) -> bool {
+    match (snake.body.first(), snake.body.last()) {
+        (Some(head), Some(tail)) => a_star(board, head, tail, false, |_| 1).is_some(),
+        _ => false,
+    }
+}
+
+// Plies of our own moves looked at by future_flexibility
+const FLEXIBILITY_DEPTH: u32 = 2;
+
+// How many safe moves we can expect from where our head is, averaged over the
+// next `depth` plies: a cell with exits that themselves lead somewhere beats one
+// whose exits are dead ends, even when both offer the same moves right away
+fn future_flexibility(board: &Board, you: &Snake, depth: u32) -> f64 {
+    let safe = safe_moves(you, board);
+    if depth <= 1 || safe.is_empty() {
+        return safe.len() as f64;
+    }
+    let deeper = safe
+        .iter()
+        .map(|dir| {
+            let (after, moved) = step_onto(&get_new_position(&you.body[0], *dir), you, board);
+            future_flexibility(&after, &moved, depth - 1)
+        })
+        .sum::<f64>()
+        / safe.len() as f64;
+    (safe.len() as f64 + deeper) / 2.0
+}
+
+// Whether the space reachable from `pos` is too small to hold our body after growing by `growth`
+fn is_trap(pos: &Coord, board: &Board, you: &Snake, growth: usize, config: &Config) -> bool {
+    let space = reachable_space(board, pos, config);
+    (space as usize) < you.body.len() + growth
+}
+
+// Flood fill from `start` with the configured cell cap and blocker model
+fn reachable_space(board: &Board, start: &Coord, config: &Config) -> i32 {
+    let blocked = blocked_cells(board, config.model_opponent_growth);
+    flood_region(board, start, &blocked, config.flood_fill_cap(board)).len() as i32
+}
+
+// Cells reachable from `start`, the same count with hazard cells weighted by
+// HAZARD_SPACE_WEIGHT, and the food inside that region
+fn estimate_space(board: &Board, start: &Coord, config: &Config) -> (i32, f64, usize) {
+    let blocked = blocked_cells(board, config.model_opponent_growth);
+    let region = flood_region(board, start, &blocked, config.flood_fill_cap(board));
+    let hazards = region.iter().filter(|cell| board.hazards.contains(cell)).count();
+    let usable = (region.len() - hazards) as f64 + hazards as f64 * config.hazard_space_weight;
+    let food = board.food.iter().filter(|food| region.contains(food)).count();
+    (region.len() as i32, usable, food)
+}
+
+// The connected open cells around `start`, stopping once `max_cells` have been collected
+fn flood_region(board: &Board, start: &Coord, blocked: &HashSet<Coord>, max_cells: usize) -> HashSet<Coord> {
+    let mut region = HashSet::new();
+    let mut stack = vec![start.clone()];
+    while let Some(cell) = stack.pop() {
+        if region.len() >= max_cells {
+            break;
+        }
+        if region.contains(&cell) {
+            continue;
+        }
+        let in_bounds = cell.x >= 0 && cell.x < board.width && cell.y >= 0 && cell.y < board.height;
+        if !in_bounds || (cell != *start && blocked.contains(&cell)) {
+            continue;
+        }
+        for dir in Direction::all() {
+            stack.push(get_new_position(&cell, dir));
+        }
+        region.insert(cell);
+    }
+    region
+}
+
+// The open cells of a board split into orthogonally connected components
+struct Components {
+    // Size of each component, indexed by component id
+    sizes: Vec<usize>,
+    // Components each snake's head opens onto, by snake id. A head is itself
+    // occupied, so it can border several.
+    heads: HashMap<String, Vec<usize>>,
+}
+
+impl Components {
+    // Open cells reachable from a snake's head, over every component it borders
+    fn space_for(&self, snake_id: &str) -> usize {
+        self.heads
+            .get(snake_id)
+            .map_or(0, |ids| ids.iter().map(|id| self.sizes[*id]).sum())
+    }
+
+    // Whether no opponent's head opens onto any component ours does
+    fn separated(&self, snake_id: &str) -> bool {
+        let Some(ours) = self.heads.get(snake_id) else {
+            return false;
+        };
+        self.heads
+            .iter()
+            .filter(|(id, _)| id.as_str() != snake_id)
+            .all(|(_, theirs)| theirs.iter().all(|id| !ours.contains(id)))
+    }
+}
+
+fn board_components(board: &Board) -> Components {
+    let blocked = blocked_cells(board, false);
+    let area = (board.width.max(0) * board.height.max(0)) as usize;
+    let mut labels: HashMap<Coord, usize> = HashMap::new();
+    let mut sizes = Vec::new();
+    for y in 0..board.height {
+        for x in 0..board.width {
+            let cell = Coord { x, y };
+            if blocked.contains(&cell) || labels.contains_key(&cell) {
+                continue;
+            }
+            let region = flood_region(board, &cell, &blocked, area);
+            sizes.push(region.len());
+            labels.extend(region.into_iter().map(|cell| (cell, sizes.len() - 1)));
+        }
+    }
+
+    let heads = board
+        .snakes
+        .iter()
+        .filter_map(|snake| {
+            let head = snake.body.first()?;
+            let mut ids: Vec<usize> = Direction::all()
+                .into_iter()
+                .filter_map(|dir| labels.get(&get_new_position(head, dir)).copied())
+                .collect();
+            ids.sort_unstable();
+            ids.dedup();
+            Some((snake.id.clone(), ids))
+        })
+        .collect();
+    Components { sizes, heads }
+}
+
+// Добавьте эту функцию для проверки безопасности хода
+fn is_move_safe(new_pos: &Coord, board: &Board) -> bool {
+    // Проверка на выход за пределы поля
+    if new_pos.x < 0 || new_pos.x >= board.width || new_pos.y < 0 || new_pos.y >= board.height {
+        return false;
+    }
+
+    // Проверка столкновений со змеями
+    for snake in &board.snakes {
+        for (i, segment) in snake.body.iter().enumerate() {
+            // Пропускаем последний сегмент хвоста, если он сдвинется на этом ходу
+            if i == snake.body.len() - 1 && tail_will_move(snake) {
+                continue;
+            }
+            if new_pos == segment {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+// A tail vacates its cell next turn unless the snake just ate: the engine then
+// stacks the new segment on the tail, so the last two coordinates are equal
+fn tail_will_move(snake: &Snake) -> bool {
+    match snake.body.as_slice() {
+        [.., before_tail, tail] => before_tail != tail,
+        [_] => true,
+        [] => false,
+    }
+}
+
+// Whether the snake's head is one step from food, so it may eat next turn
+fn next_to_food(snake: &Snake, board: &Board) -> bool {
+    snake
+        .body
+        .first()
+        .is_some_and(|head| board.food.iter().any(|food| manhattan_distance(food, head) == 1))
+}
+
+// Cells occupied by snake bodies next turn, deduplicated (stacked segments
+// collapse into one cell) and without tails that are about to move. With
+// `model_growth`, a snake whose head touches food is assumed to eat, so its
+// tail stays put as well.
+fn blocked_cells(board: &Board, model_growth: bool) -> HashSet<Coord> {
+    let mut blocked = HashSet::new();
+    for snake in &board.snakes {
+        let about_to_eat = model_growth && next_to_food(snake, board);
+        let keep = if tail_will_move(snake) && !about_to_eat {
+            snake.body.len().saturating_sub(1)
+        } else {
+            snake.body.len()
+        };
+        blocked.extend(snake.body.iter().take(keep).cloned());
+    }
+    blocked
+}
+
+// API endpoints
+async fn index(config: web::Data<Config>) -> HttpResponse {
+    let appearance = &config.appearance;
+    HttpResponse::Ok().json(serde_json::json!({
+        "apiversion": "1",
+        "author": appearance.author,
+        "color": appearance.color,
+        "head": appearance.head,
+        "tail": appearance.tail,
+    }))
+}
+
+async fn start(_state: web::Json<GameState>, config: web::Data<Config>, app: web::Data<AppState>) -> HttpResponse {
+    app.metrics.lock().unwrap().games_started += 1;
+    HttpResponse::Ok().json(StartResponse {
+        color: config.appearance.color.clone(),
+    })
+}
+
+async fn r#move(
+    state: web::Json<GameState>,
+    config: web::Data<Config>,
+    weights: web::Data<RwLock<Weights>>,
+    app: web::Data<AppState>,
+) -> HttpResponse {
+    // Every log line emitted while deciding carries the game id and turn
+    let span = tracing::info_span!("move", game_id = %state.game.id, turn = state.turn);
+    let config = with_live_weights(&config, &weights);
+    let mut state = state.into_inner();
+    span.in_scope(|| normalize_ids(&mut state, &config));
+
+    let memory = app.games.lock().unwrap().get(&state.game.id).cloned().unwrap_or_default();
+    if config.track_predictions {
+        let (hits, checked) = memory.score_predictions(&state.board);
+        if checked > 0 {
+            span.in_scope(|| info!(hits, checked, "opponent predictions checked"));
+        }
+        let mut metrics = app.metrics.lock().unwrap();
+        metrics.predictions += checked;
+        metrics.prediction_hits += hits;
+    }
+    let started = Instant::now();
+    let detailed = app.log_sampler.lock().unwrap().chance(config.log_sample_rate);
+    let decision = if config.offload_compute {
+        let (state, config, span) = (state.clone(), config.clone(), span.clone());
+        web::block(move || span.in_scope(|| decide_turn(&state, &config, &memory, detailed))).await.ok()
+    } else {
+        Some(span.in_scope(|| decide_turn(&state, &config, &memory, detailed)))
+    };
+    // Entered only now so the guard is never held across the await above
+    let _enter = span.enter();
+    let (decision, predictions) = decision.unwrap_or_else(|| {
+        error!("blocking pool dropped the decision, falling back to a safe move");
+        (Decision::forced(fallback_move(&state, &config), "panic fallback"), None)
+    });
+    let decision = decision.for_origin(config.coord_origin);
+    let latency = started.elapsed();
+    let chosen_move = decision.direction;
+    if decision.is_fallback() {
+        record_fallback(&state, chosen_move, decision.reason, &config);
+    }
+    {
+        let mut metrics = app.metrics.lock().unwrap();
+        metrics.moves += 1;
+        metrics.fallback_moves += decision.is_fallback() as u64;
+        metrics.search_overrides += decision.overrides_greedy() as u64;
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        metrics.total_latency_ms += latency_ms;
+        metrics.max_latency_ms = metrics.max_latency_ms.max(latency_ms);
+    }
+    if let Some(head) = state.you.body.first() {
+        let space = reachable_space(&state.board, head, &config);
+        let mut games = app.games.lock().unwrap();
+        let memory = games.entry(state.game.id.clone()).or_default();
+        memory.record_turn(&state.you, space, decision.target.clone());
+        if let Some(predictions) = predictions {
+            memory.predicted_heads = predictions;
+        }
+    }
+    info!(r#move = %chosen_move, latency_ms = latency.as_millis() as u64, "move chosen");
+    HttpResponse::Ok().json(MoveResponse {
+        r#move: chosen_move,
+        debug: config.debug.then(|| DebugInfo::from_decision(&decision, latency)),
+    })
+}
+
+// Trims snake ids and, with CASE_INSENSITIVE_IDS, gives the board entry that
+// matches `you` regardless of case our exact id, so every `snake.id == you.id`
+// check downstream finds us. Proxies and test tools sometimes mangle ids.
+fn normalize_ids(state: &mut GameState, config: &Config) {
+    let exact_match = state.board.snakes.iter().any(|snake| snake.id == state.you.id);
+    state.you.id = state.you.id.trim().to_string();
+    for snake in &mut state.board.snakes {
+        snake.id = snake.id.trim().to_string();
+        if config.case_insensitive_ids && snake.id.eq_ignore_ascii_case(&state.you.id) {
+            snake.id = state.you.id.clone();
+        }
+    }
+    let normalized_match = state.board.snakes.iter().any(|snake| snake.id == state.you.id);
+    if normalized_match && !exact_match {
+        warn!(id = %state.you.id, "our snake only matched the board after normalizing ids");
+    }
+}
+
+// Keeps the payload of a fallback turn so it can be replayed later
+fn record_fallback(state: &GameState, chosen_move: Direction, reason: &str, config: &Config) {
+    let line = serde_json::json!({ "state": state, "move": chosen_move.as_str() }).to_string();
+    let Some(path) = &config.fallback_log else {
+        error!(reason, payload = %line, "fallback move");
+        return;
+    };
+    let written = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{line}"));
+    match written {
+        Ok(()) => error!(reason, path = %path, "fallback move, payload saved"),
+        Err(err) => error!(reason, payload = %line, "fallback move, could not write {path}: {err}"),
+    }
+}
+
+async fn metrics(app: web::Data<AppState>) -> HttpResponse {
+    let snapshot = app.metrics.lock().unwrap().clone();
+    HttpResponse::Ok().json(snapshot)
+}
+
+// Periodically logs shared-state invariants so leaks show up in long-running
+// servers, e.g. games that started but never received /end
+async fn self_check(app: web::Data<AppState>, interval: Duration) {
+    let mut ticker = actix_web::rt::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let active_games = app.games.lock().unwrap().len();
+        let metrics = app.metrics.lock().unwrap().clone();
+        info!(
+            active_games,
+            games_started = metrics.games_started,
+            games_ended = metrics.games_ended,
+            moves = metrics.moves,
+            fallback_moves = metrics.fallback_moves,
+            search_overrides = metrics.search_overrides,
+            "self check"
+        );
+    }
+}
+
+async fn end(state: web::Json<GameState>, app: web::Data<AppState>) -> HttpResponse {
+    let span = tracing::info_span!("end", game_id = %state.game.id, turn = state.turn);
+    let _enter = span.enter();
+
+    let survived = state.board.snakes.iter().any(|snake| snake.id == state.you.id);
+    let opponents_left = state.board.snakes.iter().filter(|snake| snake.id != state.you.id).count();
+    info!(
+        turns = state.turn,
+        survived,
+        length = state.you.body.len(),
+        health = state.you.health,
+        opponents_left,
+        "game over"
+    );
+
+    app.games.lock().unwrap().remove(&state.game.id);
+    let mut metrics = app.metrics.lock().unwrap();
+    metrics.games_ended += 1;
+    metrics.wins += (survived && opponents_left == 0) as u64;
+    metrics.losses += !survived as u64;
+    HttpResponse::Ok().json(serde_json::json!({}))
+}
+
+// One line of a replay file: the state we were sent and the move we answered
+#[derive(Deserialize)]
+struct ReplayTurn {
+    state: GameState,
+    r#move: String,
+}
+
+#[derive(Deserialize)]
+struct ReplayRequest {
+    path: String,
+}
+
+#[derive(Serialize)]
+struct ReplayDiff {
+    turn: i32,
+    recorded: String,
+    current: String,
+}
+
+#[derive(Serialize)]
+struct ReplaySummary {
+    turns: usize,
+    differing: Vec<ReplayDiff>,
+}
+
+// Re-runs every recorded turn through `decide` and reports where the answer changed
+fn replay_file(path: &str, config: &Config) -> Result<ReplaySummary, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+
+    let mut summary = ReplaySummary { turns: 0, differing: Vec::new() };
+    let mut games: HashMap<String, GameMemory> = HashMap::new();
+    for (line_no, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut recorded: ReplayTurn = serde_json::from_str(line)
+            .map_err(|e| format!("{path}:{}: invalid replay line: {e}", line_no + 1))?;
+        normalize_ids(&mut recorded.state, config);
+
+        let memory = games.entry(recorded.state.game.id.clone()).or_default();
+        let decision = explain_decision(&recorded.state, config, memory).for_origin(config.coord_origin);
+        let current = decision.direction;
+        let space = recorded.state.you.body.first().map_or(0, |head| reachable_space(&recorded.state.board, head, config));
+        memory.record_turn(&recorded.state.you, space, decision.target);
+        summary.turns += 1;
+        if current.as_str() != recorded.r#move {
+            summary.differing.push(ReplayDiff {
+                turn: recorded.state.turn,
+                recorded: recorded.r#move,
+                current: current.as_str().to_string(),
+            });
+        }
+    }
+
+    Ok(summary)
+}
+
+async fn debug_replay(
+    request: web::Json<ReplayRequest>,
+    config: web::Data<Config>,
+    weights: web::Data<RwLock<Weights>>,
+) -> HttpResponse {
+    if !config.debug {
+        return HttpResponse::NotFound().finish();
+    }
+    let config = with_live_weights(&config, &weights);
+
+    // Reading the file and deciding every turn would stall the worker, so both
+    // run on the blocking pool
+    let requested = request.into_inner().path;
+    let replayed = web::block(move || {
+        let path = replay_path(&requested, &config)?;
+        replay_file(&path.to_string_lossy(), &config)
+    })
+    .await;
+    match replayed {
+        Ok(Ok(summary)) => {
+            info!(turns = summary.turns, differing = summary.differing.len(), "replay finished");
+            HttpResponse::Ok().json(summary)
+        }
+        Ok(Err(message)) => HttpResponse::BadRequest().json(serde_json::json!({ "error": message })),
+        Err(e) => {
+            error!(error = %e, "replay did not finish");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// `requested` resolved against REPLAY_DIR, refused when that is unset or when
+// the path leads out of it, by `..`, an absolute path or a symlink
+fn replay_path(requested: &str, config: &Config) -> Result<std::path::PathBuf, String> {
+    let dir = config.replay_dir.as_ref().ok_or("replays are disabled without REPLAY_DIR")?;
+    let dir = std::fs::canonicalize(dir).map_err(|e| format!("cannot open REPLAY_DIR {dir}: {e}"))?;
+    let path = std::fs::canonicalize(dir.join(requested)).map_err(|e| format!("cannot read {requested}: {e}"))?;
+    if !path.starts_with(&dir) {
+        return Err(format!("{requested} is outside REPLAY_DIR"));
+    }
+    Ok(path)
+}
+
+// The startup config with whatever weights /debug/weights last installed
+fn with_live_weights(config: &Config, weights: &RwLock<Weights>) -> Config {
+    Config {
+        weights: weights.read().unwrap().clone(),
+        ..config.clone()
+    }
+}
+
+// Replaces the active weights; every later /move and replay uses them
+async fn debug_weights(
+    request: web::Json<Weights>,
+    config: web::Data<Config>,
+    weights: web::Data<RwLock<Weights>>,
+) -> HttpResponse {
+    if !config.debug {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let new_weights = request.into_inner();
+    info!(weights = ?new_weights, "weights replaced");
+    *weights.write().unwrap() = new_weights.clone();
+    HttpResponse::Ok().json(new_weights)
+}
+
+// Small deterministic PRNG (xorshift64*) so fuzz runs can be reproduced from their seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Uniform in lo..=hi
+    fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        lo + (self.next_u64() % (hi - lo + 1) as u64) as i32
+    }
+
+    // True with probability `p`
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+// Fixed seed, so log sampling picks the same turns on every run
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(1)
+    }
+}
+
+// Random but realistic state: every snake body is either a contiguous walk of
+// distinct cells or three segments stacked at spawn, no two snakes overlap, and
+// `you` is the first snake
+fn random_game_state(rng: &mut Rng) -> GameState {
+    let width = rng.range(3, 15);
+    let height = rng.range(3, 15);
+    let mut occupied: HashSet<Coord> = HashSet::new();
+    let mut snakes = Vec::new();
+
+    for index in 0..rng.range(1, 4) {
+        let start = Coord { x: rng.range(0, width - 1), y: rng.range(0, height - 1) };
+        if occupied.contains(&start) {
+            continue;
+        }
+        occupied.insert(start.clone());
+        // Freshly spawned snakes have all three segments stacked on one cell
+        if rng.range(0, 3) == 0 {
+            let body = vec![start.clone(), start.clone(), start];
+            snakes.push(Snake { id: format!("snake-{index}"), body, health: 100, customizations: None });
+            continue;
+        }
+        let mut body = vec![start];
+        for _ in 1..rng.range(1, 8) {
+            let last = body[body.len() - 1].clone();
+            let free: Vec<Coord> = Direction::all()
+                .into_iter()
+                .map(|dir| get_new_position(&last, dir))
+                .filter(|cell| cell.x >= 0 && cell.x < width && cell.y >= 0 && cell.y < height)
+                .filter(|cell| !occupied.contains(cell))
+                .collect();
+            if free.is_empty() {
+                break;
+            }
+            let next = free[rng.range(0, free.len() as i32 - 1) as usize].clone();
+            occupied.insert(next.clone());
+            body.push(next);
+        }
+        snakes.push(Snake { id: format!("snake-{index}"), body, health: rng.range(1, 100), customizations: None });
+    }
+
+    let mut random_free_cells = |count: i32| -> Vec<Coord> {
+        (0..count)
+            .map(|_| Coord { x: rng.range(0, width - 1), y: rng.range(0, height - 1) })
+            .filter(|cell| !occupied.contains(cell))
+            .collect()
+    };
+    let food = random_free_cells(3);
+    let hazards = random_free_cells(3);
+
+    GameState {
+        game: Game {
+            id: "fuzz".to_string(),
+            ruleset: Ruleset::default(),
+            timeout: default_timeout(),
+            source: String::new(),
+        },
+        turn: 0,
+        you: snakes[0].clone(),
+        board: Board { height, width, food, hazards, snakes },
+    }
+}
+
+// Property: whenever a non-reversing move avoids walls and bodies, `decide` picks one
+fn fuzz_decisions(iterations: u32, seed: u64, config: &Config) -> Result<(), String> {
+    let mut rng = Rng::new(seed);
+    for iteration in 0..iterations {
+        let state = random_game_state(&mut rng);
+        let safe = safe_moves(&state.you, &state.board);
+
+        let chosen = decide(&state, config, &GameMemory::default());
+        if !safe.is_empty() && !safe.contains(&chosen) {
+            return Err(format!(
+                "iteration {iteration} (seed {seed}): chose {chosen} but safe moves were {safe:?} on a {}x{} board",
+                state.board.width, state.board.height
+            ));
+        }
+    }
+    Ok(())
+}
+
+// A 5x5 board walled off at y=3 by a longer snake, with ours below the wall
+const SELF_TEST_BOARD: &str = r#"{
+    "game": {"id": "self-test"},
+    "turn": 10,
+    "board": {
+        "height": 5,
+        "width": 5,
+        "food": [],
+        "snakes": [
+            {"id": "us", "health": 90, "body": [{"x": 2, "y": 1}, {"x": 2, "y": 0}, {"x": 1, "y": 0}]},
+            {"id": "wall", "health": 90, "body": [
+                {"x": 0, "y": 3}, {"x": 1, "y": 3}, {"x": 2, "y": 3}, {"x": 3, "y": 3}, {"x": 4, "y": 3}, {"x": 4, "y": 4}
+            ]}
+        ]
+    },
+    "you": {"id": "us", "health": 90, "body": [{"x": 2, "y": 1}, {"x": 2, "y": 0}, {"x": 1, "y": 0}]}
+}"#;
+
+// Checks the core helpers against the built-in board, reporting the first mismatch
+fn self_test(config: &Config) -> Result<(), String> {
+    let origin = Coord { x: 2, y: 2 };
+    let step = |dir| {
+        let next = get_new_position(&origin, dir);
+        (next.x - origin.x, next.y - origin.y)
+    };
+    let up = step(Direction::Up);
+    let vertical = up.0 == 0 && up.1.abs() == 1 && step(Direction::Down) == (0, -up.1);
+    if !vertical || step(Direction::Left) != (-1, 0) || step(Direction::Right) != (1, 0) {
+        return Err(format!("get_new_position: unexpected steps from {origin:?}, up went {up:?}"));
+    }
+
+    let state: GameState = serde_json::from_str(SELF_TEST_BOARD).map_err(|e| format!("fixture: {e}"))?;
+    let board = &state.board;
+    let expected_safety = [
+        (Coord { x: 2, y: 2 }, true),
+        (Coord { x: 2, y: 3 }, false),
+        (Coord { x: 2, y: 0 }, false),
+        (Coord { x: -1, y: 1 }, false),
+        (Coord { x: 0, y: 1 }, true),
+    ];
+    for (cell, safe) in expected_safety {
+        if is_move_safe(&cell, board) != safe {
+            return Err(format!("is_move_safe({cell:?}): expected {safe}"));
+        }
+    }
+
+    // Below the wall, minus the two body cells that stay put; our tail moves on
+    let space = reachable_space(board, &Coord { x: 2, y: 2 }, config);
+    if space != 13 {
+        return Err(format!("reachable_space: expected 13 cells, counted {space}"));
+    }
+
+    let chosen = decide(&state, config, &GameMemory::default());
+    let safe = safe_moves(&state.you, board);
+    if !safe.contains(&chosen) {
+        return Err(format!("decide: chose {chosen} but safe moves were {safe:?}"));
+    }
+    Ok(())
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    // Log level is configurable via RUST_LOG, e.g. RUST_LOG=debug for per-direction scores
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+
+    let config = match Config::load() {
+        Ok(config) => web::Data::new(config),
+        Err(e) => {
+            error!(error = %e, "invalid configuration with STRICT_CONFIG, refusing to start");
+            std::process::exit(1);
+        }
+    };
+
+    // `--fuzz <iterations> [seed]` checks decisions on random boards instead of serving
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--fuzz") {
+        let iterations = args.get(2).and_then(|n| n.parse().ok()).unwrap_or(1000);
+        let seed = args.get(3).and_then(|n| n.parse().ok()).unwrap_or(1);
+        match fuzz_decisions(iterations, seed, &config) {
+            Ok(()) => {
+                info!(iterations, seed, "fuzz run passed");
+                std::process::exit(0);
+            }
+            Err(failure) => {
+                error!(%failure, "fuzz run found an illegal move");
+                std::process::exit(1);
+            }
+        }
+    }
+    // `--selftest` (or SELFTEST=1) checks the core helpers on a built-in board instead of serving
+    if args.get(1).map(String::as_str) == Some("--selftest") || env_flag(&process_env, "SELFTEST") == Ok(Some(true)) {
+        match self_test(&config) {
+            Ok(()) => {
+                info!("self-test passed");
+                std::process::exit(0);
+            }
+            Err(failure) => {
+                error!(%failure, "self-test failed");
+                std::process::exit(1);
+            }
+        }
+    }
+    let address = (config.host.clone(), config.port);
+    let app_state = web::Data::new(AppState::default());
+    let weights = web::Data::new(RwLock::new(config.weights.clone()));
+    if let Some(seconds) = config.self_check_interval.filter(|seconds| *seconds > 0) {
+        actix_web::rt::spawn(self_check(app_state.clone(), Duration::from_secs(seconds)));
+    }
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(config.clone())
+            .app_data(app_state.clone())
+            .app_data(weights.clone())
+            .route("/", web::get().to(index))
+            .route("/start", web::post().to(start))
+            .route("/move", web::post().to(r#move))
+            .route("/end", web::post().to(end))
+            .route("/metrics", web::get().to(metrics))
+            .route("/debug/replay", web::post().to(debug_replay))
+            .route("/debug/weights", web::post().to(debug_weights))
+    })
+    .bind(address)?
+    .run()
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    fn coords(cells: &[(i32, i32)]) -> Vec<Coord> {
+        cells.iter().map(|&(x, y)| Coord { x, y }).collect()
+    }
+
+    fn snake(id: &str, cells: &[(i32, i32)]) -> Snake {
+        Snake { id: id.to_string(), body: coords(cells), health: 100, customizations: None }
+    }
+
+    // The search at exactly `depth`, without iterative deepening going further
+    fn fixed_depth(depth: u32) -> Config {
+        Config { search_depth: depth, max_search_depth: depth, ..Config::default() }
+    }
+
+    // For calling the search directly without a time limit getting in the way
+    fn no_deadline() -> Instant {
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    // A standard game where we are the first of `snakes`
+    fn game_state(width: i32, height: i32, snakes: Vec<Snake>, food: &[(i32, i32)]) -> GameState {
+        GameState {
+            game: Game { id: "test".to_string(), ruleset: Ruleset::default(), timeout: 500, source: String::new() },
+            turn: 10,
+            board: Board { height, width, food: coords(food), hazards: Vec::new(), snakes: snakes.clone() },
+            you: snakes[0].clone(),
+        }
+    }
+
+    // Log output collected in memory, for asserting on what a handler logged
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[actix_web::test]
+    async fn move_logs_carry_the_game_id_and_turn() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(Weights::default())))
+                .app_data(web::Data::new(Config::default()))
+                .app_data(web::Data::new(AppState::default()))
+                .route("/move", web::post().to(r#move)),
+        )
+        .await;
+        let us = serde_json::json!({ "id": "us", "body": [{ "x": 5, "y": 5 }, { "x": 5, "y": 4 }, { "x": 5, "y": 3 }], "health": 100 });
+        let state = serde_json::json!({
+            "game": { "id": "game-1234" },
+            "turn": 10,
+            "board": { "height": 11, "width": 11, "food": [], "snakes": [us] },
+            "you": us,
+        });
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        assert!(response.status().is_success());
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let chosen = output.lines().find(|line| line.contains("move chosen")).expect("move logged");
+        assert!(chosen.contains("game_id=game-1234") && chosen.contains("turn=10"), "{chosen}");
+    }
+
+    #[test]
+    fn first_step_heads_around_a_wall() {
+        // A wall runs across x = 0..=3 right above our head, open on the right
+        let wall = snake("wall", &[(0, 3), (1, 3), (2, 3), (3, 3), (3, 4), (3, 5)]);
+        let you = snake("us", &[(1, 2), (1, 1), (1, 0)]);
+        let state = game_state(7, 7, vec![you.clone(), wall], &[(1, 6)]);
+        let food = Coord { x: 1, y: 6 };
+        let step = first_step_of_path(&state.board, &you.body[0], &food, false, |_| 1);
+        assert_eq!(step, Some(Direction::Right));
+        let (_, dir, _) = evaluate_food(&you.body[0], &state.board, &you, &state.game.ruleset, &Config::default()).expect("food");
+        assert_eq!(dir, Direction::Right);
+
+        // Across the full width, the food is out of reach
+        let sealed = snake("wall", &[(0, 3), (1, 3), (2, 3), (3, 3), (4, 3), (5, 3), (6, 3), (6, 2)]);
+        let state = game_state(7, 7, vec![you.clone(), sealed], &[(1, 6)]);
+        assert_eq!(first_step_of_path(&state.board, &you.body[0], &food, false, |_| 1), None);
+    }
+
+    #[test]
+    fn flood_fill_stops_at_the_cap() {
+        let us = snake("us", &[(12, 12), (12, 11), (12, 10)]);
+        let state = game_state(25, 25, vec![us], &[]);
+        let start = Coord { x: 0, y: 0 };
+        // Everything but our head and neck: the tail moves
+        let uncapped = reachable_space(&state.board, &start, &Config::default());
+        assert_eq!(uncapped, 25 * 25 - 2);
+        for cap in [1, 10, 100] {
+            let config = Config { flood_fill_max_cells: Some(cap), ..Config::default() };
+            assert_eq!(reachable_space(&state.board, &start, &config), cap as i32);
+        }
+    }
+
+    #[test]
+    fn stacked_tail_stays_blocked_for_a_turn() {
+        // Just ate: the new segment sits on the tail cell
+        let fed = snake("us", &[(3, 3), (3, 2), (3, 1), (3, 1)]);
+        assert!(!tail_will_move(&fed));
+        let state = game_state(7, 7, vec![fed.clone()], &[]);
+        let blocked = blocked_cells(&state.board, false);
+        assert_eq!(blocked.len(), 3);
+        assert!(blocked.contains(&Coord { x: 3, y: 1 }));
+
+        let moving = snake("us", &[(3, 3), (3, 2), (3, 1)]);
+        assert!(tail_will_move(&moving));
+        let state = game_state(7, 7, vec![moving], &[]);
+        assert!(!blocked_cells(&state.board, false).contains(&Coord { x: 3, y: 1 }));
+    }
+
+    #[actix_web::test]
+    async fn replays_only_read_from_the_replay_directory() {
+        let root = std::env::temp_dir().join(format!("replays-{}", std::process::id()));
+        let dir = root.join("recorded");
+        std::fs::create_dir_all(&dir).unwrap();
+        let us = serde_json::json!({ "id": "us", "body": [{ "x": 5, "y": 5 }, { "x": 5, "y": 4 }, { "x": 5, "y": 3 }], "health": 100 });
+        let state = serde_json::json!({
+            "game": { "id": "game-1234" },
+            "turn": 10,
+            "board": { "height": 11, "width": 11, "food": [], "snakes": [us] },
+            "you": us,
+        });
+        let line = serde_json::json!({ "state": state, "move": "up" }).to_string();
+        std::fs::write(dir.join("game.jsonl"), &line).unwrap();
+        std::fs::write(root.join("secret.jsonl"), &line).unwrap();
+
+        let replay = |replay_dir: Option<&std::path::Path>, path: String| {
+            let config = Config { debug: true, replay_dir: replay_dir.map(|dir| dir.to_string_lossy().into_owned()), ..Config::default() };
+            async move {
+                let app = actix_web::test::init_service(
+                    App::new()
+                        .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                        .app_data(web::Data::new(config))
+                        .route("/debug/replay", web::post().to(debug_replay)),
+                )
+                .await;
+                let request = actix_web::test::TestRequest::post()
+                    .uri("/debug/replay")
+                    .set_json(serde_json::json!({ "path": path }))
+                    .to_request();
+                let response = actix_web::test::call_service(&app, request).await;
+                let status = response.status();
+                let body: serde_json::Value = actix_web::test::read_body_json(response).await;
+                (status, body)
+            }
+        };
+
+        let (status, body) = replay(Some(&dir), "game.jsonl".to_string()).await;
+        assert!(status.is_success(), "{body}");
+        assert_eq!(body["turns"], 1);
+
+        let escapes = ["../secret.jsonl".to_string(), root.join("secret.jsonl").to_string_lossy().into_owned()];
+        for path in escapes {
+            let (status, body) = replay(Some(&dir), path).await;
+            assert_eq!(status, actix_web::http::StatusCode::BAD_REQUEST);
+            assert!(body["error"].as_str().unwrap_or_default().contains("outside REPLAY_DIR"), "{body}");
+        }
+        let (status, body) = replay(None, "game.jsonl".to_string()).await;
+        assert_eq!(status, actix_web::http::StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap_or_default().contains("REPLAY_DIR"), "{body}");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn static_only_blend_reproduces_the_greedy_move() {
+        let config = Config { search_blend: 1.0, ..Config::default() };
+        let states = [
+            game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[(8, 5)]),
+            game_state(11, 11, vec![snake("us", &[(0, 0), (1, 0), (2, 0)]), snake("them", &[(3, 2), (3, 3), (3, 4), (3, 5)])], &[(0, 5)]),
+            game_state(7, 7, vec![snake("us", &[(3, 3), (3, 2), (2, 2), (2, 3)]), snake("them", &[(5, 5), (5, 6), (6, 6)])], &[(4, 4)]),
+        ];
+        let memory = GameMemory::default();
+        for state in &states {
+            let terms = PositionTerms::new(state, &config, &memory);
+            let moves = Direction::all().into_iter().map(Move::new).collect();
+            let greedy = best_move(evaluate_moves(moves, state, &terms));
+            assert_eq!(bilinear_duel(state, &config, &memory, no_deadline()).direction, greedy.direction);
+        }
+    }
+
+    #[test]
+    fn paths_detour_around_costly_hazards() {
+        let mut state = game_state(7, 7, vec![snake("us", &[(3, 0), (2, 0), (1, 0)])], &[]);
+        state.board.hazards = coords(&[(1, 2), (2, 2), (3, 2), (4, 2), (5, 2)]);
+        let (from, to) = (Coord { x: 3, y: 0 }, Coord { x: 3, y: 4 });
+        let path_with_damage = |damage| {
+            let mut ruleset = Ruleset::default();
+            ruleset.settings.hazard_damage_per_turn = damage;
+            a_star(&state.board, &from, &to, false, hazard_step_cost(&state.board, &ruleset)).expect("reachable")
+        };
+
+        // Ten open steps around the strip beat four with a 14-damage hazard
+        let detour = path_with_damage(14);
+        assert_eq!(detour.len(), 10);
+        assert!(detour.iter().all(|cell| !state.board.hazards.contains(cell)));
+        // A cheap hazard is worth crossing
+        assert_eq!(path_with_damage(2).len(), 4);
+    }
+
+    #[test]
+    fn aggressive_snake_closes_in_over_two_turns() {
+        let us = snake("us", &[(5, 5), (4, 5), (3, 5), (2, 5), (1, 5), (0, 5)]);
+        let them = snake("them", &[(9, 1), (9, 0), (10, 0)]);
+        let start = game_state(11, 11, vec![us, them], &[]);
+        let gap = |state: &GameState| manhattan_distance(&state.board.snakes[0].body[0], &state.board.snakes[1].body[0]);
+        // Two turns against the greedy opponent model, checking we keep room to escape
+        let play = |personality| {
+            let config = Config { personality, ..fixed_depth(2) };
+            let mut state = start.clone();
+            for _ in 0..2 {
+                let ours = bilinear_duel(&state, &config, &GameMemory::default(), no_deadline()).direction;
+                let theirs = greedy_reply(&state.board, "them", &config);
+                state.board = simulate_turn(&state.board, &[("us", ours), ("them", theirs)]);
+                state.you = state.board.snakes[0].clone();
+                assert_eq!(state.board.snakes.len(), 2);
+                assert!(reachable_space(&state.board, &state.you.body[0], &config) as usize > state.you.body.len());
+            }
+            gap(&state)
+        };
+        let aggressive = play(Personality::Aggressive);
+        let balanced = play(Personality::Balanced);
+        assert!(aggressive < gap(&start) && aggressive < balanced, "{aggressive} vs {balanced}");
+    }
+
+    #[test]
+    fn degenerate_boards_still_score_finite() {
+        let you = snake("us", &[(0, 0), (0, 0), (0, 0)]);
+        let empty = game_state(0, 0, vec![you.clone()], &[]);
+        let origin = Coord { x: 0, y: 0 };
+        assert!(evaluate_center_control(&origin, &empty.board).is_finite());
+        assert!(evaluate_threats(&origin, &empty.board, &you, &Config::default()).is_finite());
+        assert!(calculate_food_score(f64::MAX, 10).is_finite());
+
+        // No food anywhere: every term still has to come out as a number
+        let state = game_state(3, 3, vec![snake("us", &[(1, 1), (1, 0), (0, 0)])], &[]);
+        let config = Config::default();
+        let memory = GameMemory::default();
+        let terms = PositionTerms::new(&state, &config, &memory);
+        let (score, _) = position_score(&state.board, &state.you, &terms);
+        assert!(score.is_finite());
+        assert!(sanitize(f64::NAN) == 0.0 && sanitize(f64::INFINITY).is_finite());
+    }
+
+    #[test]
+    fn opponents_go_greedy_past_their_depth() {
+        let state = game_state(
+            11,
+            11,
+            vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), snake("a", &[(1, 1), (1, 0), (0, 0)]), snake("b", &[(9, 9), (9, 10), (10, 10)])],
+            &[],
+        );
+        let alive = ["a", "b"];
+        let shallow = Config { opponent_depth: Some(1), ..fixed_depth(3) };
+        assert_eq!(opponent_replies(&state.board, &alive, 1, &shallow).len(), 16);
+        let greedy = opponent_replies(&state.board, &alive, 2, &shallow);
+        let expected = vec![greedy_reply(&state.board, "a", &shallow), greedy_reply(&state.board, "b", &shallow)];
+        assert_eq!(greedy, [expected]);
+
+        // Unset, opponents are modeled as deep as we search
+        let full = fixed_depth(3);
+        assert_eq!(opponent_replies(&state.board, &alive, 3, &full).len(), 16);
+        assert_eq!(opponent_replies(&state.board, &alive, 4, &full).len(), 1);
+    }
+
+    #[test]
+    fn directions_reverse_and_round_trip() {
+        for dir in Direction::all() {
+            assert_ne!(dir.opposite(), dir);
+            assert_eq!(dir.opposite().opposite(), dir);
+            assert_eq!(dir.as_str().parse(), Ok(dir));
+            assert_eq!(dir.to_string(), dir.as_str());
+        }
+        assert_eq!(Direction::Up.opposite(), Direction::Down);
+        assert_eq!(Direction::Left.opposite(), Direction::Right);
+        assert!("sideways".parse::<Direction>().is_err());
+    }
+
+    #[test]
+    fn health_exactly_covering_the_path_is_cutting_it_fine() {
+        // The only food is four moves straight ahead
+        let hungry = |health| game_state(11, 11, vec![Snake { health, ..snake("us", &[(5, 5), (5, 4), (5, 3)]) }], &[(5, 9)]);
+        let config = Config::default();
+        let hunger_at = |health, config: &Config| {
+            let state = hungry(health);
+            hunger(&state.you.body[0], &state.you, &state.board, &state.game.ruleset, config)
+        };
+        assert_eq!(hunger_at(4, &config), Hunger::Urgent);
+        assert_eq!(hunger_at(3, &config), Hunger::Desperate);
+        assert_eq!(hunger_at(4 + config.starvation_margin - 1, &config), Hunger::Urgent);
+        assert_eq!(hunger_at(4 + config.starvation_margin, &config), Hunger::Fed);
+        // With no margin, just enough health is enough
+        assert_eq!(hunger_at(4, &Config { starvation_margin: 0, ..Config::default() }), Hunger::Fed);
+
+        assert_eq!(bilinear_duel(&hungry(4), &config, &GameMemory::default(), no_deadline()).direction, Direction::Up);
+    }
+
+    #[actix_web::test]
+    async fn config_file_reaches_the_server_and_the_decision() {
+        let contents = r##"
+            host = "127.0.0.1"
+            port = 9000
+            personality = "aggressive"
+            search_depth = 0
+
+            [appearance]
+            color = "#123456"
+
+            [weights]
+            food = 42.0
+        "##;
+        let config = Config::from_toml(contents).expect("valid config");
+        assert_eq!((config.host.as_str(), config.port), ("127.0.0.1", 9000));
+        assert_eq!(config.personality, Personality::Aggressive);
+        assert_eq!(config.weights.food, 42.0);
+        // Unlisted settings keep their defaults
+        assert_eq!(config.appearance.head, Appearance::default().head);
+
+        // search_depth = 0 plays the one-move evaluation
+        let state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[(8, 8)]);
+        let memory = GameMemory::default();
+        let terms = PositionTerms::new(&state, &config, &memory);
+        let moves = Direction::all().into_iter().map(Move::new).collect();
+        let greedy = best_move(evaluate_moves(moves, &state, &terms));
+        assert_eq!(bilinear_duel(&state, &config, &memory, no_deadline()).direction, greedy.direction);
+
+        let app = actix_web::test::init_service(App::new().app_data(web::Data::new(config)).route("/", web::get().to(index))).await;
+        let info: serde_json::Value =
+            actix_web::test::call_and_read_body_json(&app, actix_web::test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(info["color"], "#123456");
+    }
+
+    #[test]
+    fn a_non_finite_term_leaves_the_others_counting() {
+        let state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[(8, 8)]);
+        let memory = GameMemory::default();
+        let score = |center: f64| {
+            let config = Config { weights: Weights { center, ..Weights::default() }, ..Config::default() };
+            let terms = PositionTerms::new(&state, &config, &memory);
+            position_score(&state.board, &state.you, &terms).0
+        };
+
+        // A NaN weight costs its own term, not the space term next to it
+        let without = score(0.0);
+        assert!(without > 0.0);
+        assert_eq!(score(f64::NAN), without);
+    }
+
+    #[test]
+    fn recently_visited_cells_nudge_the_choice() {
+        let state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[]);
+        let config = fixed_depth(1);
+        let fresh = bilinear_duel(&state, &config, &GameMemory::default(), no_deadline()).direction;
+
+        // We keep coming back to the cell we'd pick on a fresh board
+        let mut memory = GameMemory::default();
+        let repeated = get_new_position(&state.you.body[0], fresh);
+        for cell in [repeated.clone(), Coord { x: 5, y: 5 }, repeated, Coord { x: 5, y: 5 }] {
+            memory.record_head(&cell);
+        }
+        let nudged = bilinear_duel(&state, &config, &memory, no_deadline()).direction;
+        assert_ne!(nudged, fresh);
+    }
+
+    #[test]
+    fn eliminated_snakes_get_a_default_move() {
+        // Up runs into the wall, so only the guard would answer with it
+        let at_the_wall = || snake("us", &[(5, 10), (5, 9), (5, 8)]);
+        let mut state = game_state(11, 11, vec![at_the_wall()], &[]);
+        state.you.health = 0;
+        assert_eq!(decide(&state, &Config::default(), &GameMemory::default()), Direction::Up);
+
+        // Missing from the board counts as eliminated too
+        let mut state = game_state(11, 11, vec![at_the_wall(), snake("them", &[(1, 1), (1, 0)])], &[]);
+        state.board.snakes.remove(0);
+        assert_eq!(decide(&state, &Config::default(), &GameMemory::default()), Direction::Up);
+
+        // As is a body with nothing left in it
+        state.you.body.clear();
+        assert_eq!(decide(&state, &Config::default(), &GameMemory::default()), Direction::Up);
+    }
+
+    #[test]
+    fn center_pull_fades_over_the_game() {
+        let config = Config::default();
+        let early = config.center_weight(5);
+        let late = config.center_weight(200);
+        assert!(early > 0.0 && late < early * 0.5, "{early} vs {late}");
+        let constant = Config { weights: Weights { center_decay: 0.0, ..Weights::default() }, ..Config::default() };
+        assert_eq!(constant.center_weight(5), constant.center_weight(200));
+    }
+
+    #[test]
+    fn random_states_always_get_a_safe_move() {
+        // Short budgets keep the run quick; a timed-out search must still play safe
+        let config = Config { latency_margin_ms: 480, ..Config::default() };
+        for seed in [1, 42, 2024] {
+            assert_eq!(fuzz_decisions(40, seed, &config), Ok(()));
+        }
+    }
+
+    #[test]
+    fn drifts_away_from_a_larger_head() {
+        let you = snake("us", &[(5, 5), (5, 4), (5, 3)]);
+        let big = snake("big", &[(2, 6), (1, 6), (0, 6), (0, 7), (0, 8), (0, 9)]);
+        let state = game_state(11, 11, vec![you.clone(), big], &[]);
+        let repulsion = |cell: (i32, i32)| avoidance_from_larger(&Coord { x: cell.0, y: cell.1 }, &state.board, &you, &Config::default());
+        assert!(repulsion((4, 5)) < repulsion((6, 5)));
+        assert_eq!(repulsion((6, 5)), 0.0, "beyond the radius of influence");
+
+        // The repulsion widens the gap between heading away and heading towards it
+        let memory = GameMemory::default();
+        let margin = |config: &Config| {
+            let terms = PositionTerms::new(&state, config, &memory);
+            let moves = evaluate_moves(vec![Move::new(Direction::Left), Move::new(Direction::Right)], &state, &terms);
+            let score = |dir| moves.iter().find(|m| m.direction == dir).map_or(f64::NAN, |m| m.score);
+            score(Direction::Right) - score(Direction::Left)
+        };
+        let config = fixed_depth(1);
+        let indifferent = Config { weights: Weights { avoid_larger: 0.0, ..Weights::default() }, ..config.clone() };
+        assert!(margin(&config) > margin(&indifferent));
+        assert_eq!(bilinear_duel(&state, &config, &memory, no_deadline()).direction, Direction::Right);
+    }
+
+    #[test]
+    fn a_single_safe_move_skips_the_evaluation() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let you = snake("us", &[(0, 1), (0, 2), (0, 3)]);
+        let state = game_state(11, 11, vec![you, snake("them", &[(1, 3), (1, 2), (1, 1), (2, 1)])], &[]);
+        assert_eq!(safe_moves(&state.you, &state.board), [Direction::Down]);
+        assert_eq!(decide(&state, &Config::default(), &GameMemory::default()), Direction::Down);
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("only one safe move"), "{output}");
+        assert!(!output.contains("move evaluated"), "{output}");
+    }
+
+    #[test]
+    fn worst_case_resolution_avoids_a_tail_that_may_stay() {
+        // Their head sits next to food, so their tail at (6, 5) may not move
+        let you = snake("us", &[(5, 5), (5, 4), (5, 3)]);
+        let them = snake("them", &[(8, 6), (7, 6), (6, 6), (6, 5)]);
+        let state = game_state(11, 11, vec![you, them], &[(9, 6)]);
+        let tail_score = |config: &Config| {
+            let memory = GameMemory::default();
+            let terms = PositionTerms::new(&state, config, &memory);
+            evaluate_moves(vec![Move::new(Direction::Right)], &state, &terms)[0].score
+        };
+        assert!(tail_score(&Config::default()).is_finite());
+        let cautious = Config { assume_worst_resolution: true, ..Config::default() };
+        assert_eq!(tail_score(&cautious), f64::NEG_INFINITY);
+    }
+
+    #[actix_web::test]
+    async fn end_summarizes_the_outcome() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let app_state = web::Data::new(AppState::default());
+        let app = actix_web::test::init_service(App::new().app_data(app_state.clone()).route("/end", web::post().to(end))).await;
+
+        let us = snake("us", &[(5, 5), (5, 4), (5, 3), (5, 2)]);
+        let won = game_state(11, 11, vec![us.clone()], &[]);
+        let mut lost = game_state(11, 11, vec![us, snake("them", &[(1, 1), (1, 0)])], &[]);
+        lost.board.snakes.remove(0);
+        for state in [&won, &lost] {
+            let request = actix_web::test::TestRequest::post().uri("/end").set_json(state).to_request();
+            assert!(actix_web::test::call_service(&app, request).await.status().is_success());
+        }
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let summaries: Vec<&str> = output.lines().filter(|line| line.contains("game over")).collect();
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries[0].contains("survived=true") && summaries[0].contains("length=4") && summaries[0].contains("opponents_left=0"));
+        assert!(summaries[1].contains("survived=false") && summaries[1].contains("opponents_left=1"));
+        let metrics = app_state.metrics.lock().unwrap().clone();
+        assert_eq!((metrics.games_ended, metrics.wins, metrics.losses), (2, 1, 1));
+    }
+
+    #[test]
+    fn moves_below_the_space_floor_lose() {
+        // Left runs into the four cells of column 0, shorter than we are; right into the open
+        let state = game_state(
+            7,
+            7,
+            vec![snake("us", &[(1, 3), (1, 2), (1, 1), (1, 0), (2, 0), (3, 0)]), snake("wall", &[(2, 5), (2, 4), (1, 4), (0, 4), (0, 5)])],
+            &[],
+        );
+        let margin = |config: &Config| {
+            let memory = GameMemory::default();
+            let terms = PositionTerms::new(&state, config, &memory);
+            let moves = evaluate_moves(vec![Move::new(Direction::Left), Move::new(Direction::Right)], &state, &terms);
+            moves[1].score - moves[0].score
+        };
+        let config = Config::default();
+        let without = Config { weights: Weights { min_space_penalty: 0.0, ..Weights::default() }, ..Config::default() };
+        assert!(margin(&config) > 0.0);
+        assert!((margin(&config) - margin(&without) - config.weights.min_space_penalty).abs() < 1e-6);
+
+        // The floor defaults to our length and MIN_SPACE overrides it
+        let you = &state.you;
+        assert_eq!(config.space_floor_penalty(5, you), -config.weights.min_space_penalty);
+        assert_eq!(config.space_floor_penalty(6, you), 0.0);
+        let lenient = Config { min_space: Some(3), ..Config::default() };
+        assert_eq!(lenient.space_floor_penalty(4, you), 0.0);
+    }
+
+    #[test]
+    fn a_panicking_evaluation_falls_back_to_a_safe_move() {
+        // Up is our neck and left is off the board
+        let state = game_state(11, 11, vec![snake("us", &[(0, 5), (0, 6), (0, 7)])], &[]);
+        let decision = guarded(&state, &Config::default(), false, || panic!("evaluator bug"));
+        assert_eq!(decision.reason, "panic fallback");
+        assert!(safe_moves(&state.you, &state.board).contains(&decision.direction));
+    }
+
+    #[actix_web::test]
+    async fn move_answers_with_a_safe_move_when_the_evaluation_panics() {
+        fn buggy_evaluation() {
+            panic!("evaluator bug");
+        }
+        let app_state = web::Data::new(AppState::default());
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(Weights::default())))
+                .app_data(web::Data::new(Config::default()))
+                .app_data(app_state.clone())
+                .route("/move", web::post().to(r#move)),
+        )
+        .await;
+        // Up is our neck and left is off the board
+        let state = game_state(11, 11, vec![snake("us", &[(0, 5), (0, 6), (0, 7)])], &[]);
+
+        EVALUATION_HOOK.with(|hook| hook.set(Some(buggy_evaluation)));
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        let response = actix_web::test::call_service(&app, request).await;
+        EVALUATION_HOOK.with(|hook| hook.set(None));
+
+        assert!(response.status().is_success());
+        let body: serde_json::Value = actix_web::test::read_body_json(response).await;
+        assert!(["down", "right"].contains(&body["move"].as_str().unwrap_or_default()), "{body}");
+        assert_eq!(app_state.metrics.lock().unwrap().fallback_moves, 1);
+    }
+
+    #[test]
+    fn growing_opponents_keep_their_tails_in_space_estimates() {
+        // Their head touches food at (4, 3), so their tail at (2, 0) may stay
+        let them = snake("them", &[(4, 2), (4, 1), (4, 0), (3, 0), (2, 0)]);
+        let you = snake("us", &[(1, 0), (1, 1), (1, 2)]);
+        let state = game_state(11, 11, vec![you, them], &[(4, 3)]);
+        let tail = Coord { x: 2, y: 0 };
+        assert!(!blocked_cells(&state.board, false).contains(&tail));
+        assert!(blocked_cells(&state.board, true).contains(&tail));
+
+        let head = Coord { x: 1, y: 0 };
+        let growth = Config { model_opponent_growth: true, ..Config::default() };
+        assert_eq!(
+            reachable_space(&state.board, &head, &growth),
+            reachable_space(&state.board, &head, &Config::default()) - 1
+        );
+    }
+
+    #[actix_web::test]
+    async fn move_response_carries_debug_only_when_asked() {
+        let state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[(8, 8)]);
+        for debug in [true, false] {
+            let app = actix_web::test::init_service(
+                App::new()
+                    .app_data(web::Data::new(RwLock::new(Weights::default())))
+                    .app_data(web::Data::new(Config { debug, ..Config::default() }))
+                    .app_data(web::Data::new(AppState::default()))
+                    .route("/move", web::post().to(r#move)),
+            )
+            .await;
+            let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+            let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+            assert!(body["move"].is_string());
+            if debug {
+                assert_eq!(body["debug"]["scores"].as_object().map(|scores| scores.len()), Some(4));
+                assert!(body["debug"]["scores"]["down"].is_null(), "reversing into the neck is ruled out");
+                assert!(body["debug"]["reason"].is_string());
+            } else {
+                assert!(body.get("debug").is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn severing_tail_access_scores_lower() {
+        // Left seals us into column 0 away from our tail; right keeps a path down to it
+        let state = game_state(
+            7,
+            7,
+            vec![snake("us", &[(1, 3), (1, 2), (1, 1), (1, 0), (2, 0), (3, 0)]), snake("wall", &[(2, 5), (2, 4), (1, 4), (0, 4), (0, 5)])],
+            &[],
+        );
+        let head = &state.you.body[0];
+        let reachable = |dir| {
+            let (after, moved) = step_onto(&get_new_position(head, dir), &state.you, &state.board);
+            can_reach_own_tail(&after, &moved)
+        };
+        assert!(!reachable(Direction::Left) && reachable(Direction::Right));
+
+        let margin = |config: &Config| {
+            let memory = GameMemory::default();
+            let terms = PositionTerms::new(&state, config, &memory);
+            let moves = evaluate_moves(vec![Move::new(Direction::Left), Move::new(Direction::Right)], &state, &terms);
+            moves[1].score - moves[0].score
+        };
+        let config = Config::default();
+        let indifferent = Config { weights: Weights { tail_access: 0.0, ..Weights::default() }, ..Config::default() };
+        assert!((margin(&config) - margin(&indifferent) - config.weights.tail_access).abs() < 1e-6);
+    }
+
+    #[test]
+    fn config_file_spells_the_origin_like_the_environment() {
+        let config = Config::from_toml("coord_origin = \"top_left\"").expect("valid config");
+        assert_eq!(config.coord_origin, CoordOrigin::TopLeft);
+        assert_eq!("top_left".parse(), Ok(CoordOrigin::TopLeft));
+    }
+
+    #[actix_web::test]
+    async fn up_and_down_flip_under_a_top_left_origin() {
+        // Up from a head raises y internally, down lowers it
+        let head = Coord { x: 5, y: 5 };
+        assert_eq!(get_new_position(&head, Direction::Up), Coord { x: 5, y: 6 });
+        assert_eq!(get_new_position(&head, Direction::Down), Coord { x: 5, y: 4 });
+        for dir in Direction::all() {
+            assert_eq!(CoordOrigin::BottomLeft.engine_direction(dir), dir);
+        }
+        assert_eq!(CoordOrigin::TopLeft.engine_direction(Direction::Up), Direction::Down);
+        assert_eq!(CoordOrigin::TopLeft.engine_direction(Direction::Down), Direction::Up);
+        assert_eq!(CoordOrigin::TopLeft.engine_direction(Direction::Left), Direction::Left);
+
+        // In the y = 0 row with our neck to the right, the only way out is toward
+        // larger y: up from the bottom-left, down from the top-left
+        let state = game_state(11, 11, vec![snake("us", &[(0, 0), (1, 0), (2, 0)])], &[]);
+        for (coord_origin, expected) in [(CoordOrigin::BottomLeft, "up"), (CoordOrigin::TopLeft, "down")] {
+            let app = actix_web::test::init_service(
+                App::new()
+                    .app_data(web::Data::new(RwLock::new(Weights::default())))
+                    .app_data(web::Data::new(Config { coord_origin, ..Config::default() }))
+                    .app_data(web::Data::new(AppState::default()))
+                    .route("/move", web::post().to(r#move)),
+            )
+            .await;
+            let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+            let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+            assert_eq!(body["move"], expected, "{coord_origin:?}");
+        }
+    }
+
+    #[test]
+    fn sparse_boards_start_the_search_deeper() {
+        let config = Config { search_depth: 3, max_search_depth: 6, ..Config::default() };
+        let duel = game_state(11, 11, vec![snake("us", &[(1, 1), (1, 0)]), snake("them", &[(9, 9), (9, 10)])], &[]);
+        let crowded = game_state(
+            11,
+            11,
+            vec![
+                snake("us", &[(1, 1), (1, 0)]),
+                snake("a", &[(9, 9), (9, 10)]),
+                snake("b", &[(1, 9), (1, 10)]),
+                snake("c", &[(9, 1), (9, 0)]),
+            ],
+            &[],
+        );
+        assert_eq!(initial_search_depth(&duel.board, &config), 4);
+        assert_eq!(initial_search_depth(&crowded.board, &config), 2);
+        // Never past the configured maximum
+        let capped = Config { max_search_depth: 3, ..config };
+        assert_eq!(initial_search_depth(&duel.board, &capped), 3);
+    }
+
+    #[test]
+    fn food_in_the_open_beats_equally_distant_corner_food() {
+        // Both are four moves away; the corner one is listed first
+        let state = game_state(11, 11, vec![snake("us", &[(4, 1), (5, 1), (6, 1)])], &[(0, 0), (4, 5)]);
+        let head = &state.you.body[0];
+        let room = bfs_distances(&state.board, head).len();
+        let corner = food_desirability(&Coord { x: 0, y: 0 }, 5, room, &state.board);
+        let open = food_desirability(&Coord { x: 4, y: 5 }, 5, room, &state.board);
+        assert!(open > corner);
+        let (_, direction, _) = evaluate_food(head, &state.board, &state.you, &state.game.ruleset, &Config::default()).expect("reachable food");
+        assert_eq!(direction, Direction::Up);
+    }
+
+    #[test]
+    fn customizations_survive_a_round_trip() {
+        let payload = serde_json::json!({
+            "id": "them",
+            "body": [{ "x": 1, "y": 1 }, { "x": 1, "y": 0 }],
+            "health": 90,
+            "customizations": { "color": "#ff00aa", "head": "smile", "tail": "bolt" }
+        });
+        let parsed: Snake = serde_json::from_value(payload.clone()).expect("valid snake");
+        assert_eq!(parsed.customizations.as_ref().map(|c| c.head.as_str()), Some("smile"));
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), payload);
+
+        // Older payloads without the object still parse, and don't grow one
+        let mut bare = payload;
+        bare.as_object_mut().unwrap().remove("customizations");
+        let parsed: Snake = serde_json::from_value(bare.clone()).expect("valid snake");
+        assert!(parsed.customizations.is_none());
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), bare);
+    }
+
+    #[test]
+    fn threading_a_gap_picks_the_larger_side() {
+        // A wall down column 3 splits the board: three columns on the left, seven on the right
+        let wall: Vec<(i32, i32)> = (0..=8).rev().map(|y| (3, y)).chain([(4, 0)]).collect();
+        let state = game_state(11, 11, vec![snake("us", &[(3, 9), (3, 10), (4, 10)]), snake("wall", &wall)], &[]);
+        let config = fixed_depth(1);
+        let mut moves = vec![Move::new(Direction::Left), Move::new(Direction::Right)];
+        prefer_larger_region(&mut moves, &state.board, &state.you, &config);
+        assert_eq!((moves[0].score, moves[1].score), (0.0, config.weights.larger_region));
+
+        let decision = bilinear_duel(&state, &config, &GameMemory::default(), no_deadline());
+        assert_eq!(decision.direction, Direction::Right);
+    }
+
+    #[actix_web::test]
+    async fn zero_width_boards_get_a_default_move() {
+        let mut state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[(1, 1)]);
+        state.board.width = 0;
+        let decision = explain_decision(&state, &Config::default(), &GameMemory::default());
+        assert_eq!((decision.direction, decision.reason), (Direction::Up, "degenerate board"));
+
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(Weights::default())))
+                .app_data(web::Data::new(fixed_depth(1)))
+                .app_data(web::Data::new(AppState::default()))
+                .route("/move", web::post().to(r#move)),
+        )
+        .await;
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["move"], "up");
+    }
+
+    #[test]
+    fn cutoff_squeezes_a_wall_hugging_opponent() {
+        // A shorter snake runs up the left wall; we run alongside it one column in, a step ahead
+        let you = snake("us", &[(1, 5), (1, 4), (1, 3), (1, 2), (1, 1)]);
+        let them = snake("them", &[(0, 4), (0, 3), (0, 2)]);
+        let state = game_state(11, 11, vec![you.clone(), them], &[]);
+        let config = Config { personality: Personality::Aggressive, ..Config::default() };
+        assert_eq!(cutoff_target(&state.board, &you, &config).map(|snake| snake.id.as_str()), Some("them"));
+
+        let mut moves: Vec<Move> = [Direction::Up, Direction::Left, Direction::Right].into_iter().map(Move::new).collect();
+        reward_cutoff(&mut moves, &state.board, &you, &config);
+        let reduction = |dir| {
+            let after = simulate_turn(&state.board, &[("us", dir)]);
+            reachable_space(&state.board, &Coord { x: 0, y: 4 }, &config) - reachable_space(&after, &Coord { x: 0, y: 4 }, &config)
+        };
+        let rewarded = moves.iter().max_by(|a, b| a.score.total_cmp(&b.score)).map(|m| m.direction);
+        let squeezes_most = [Direction::Up, Direction::Left, Direction::Right].into_iter().max_by_key(|dir| reduction(*dir));
+        assert_eq!(rewarded, squeezes_most);
+        assert_eq!(rewarded, Some(Direction::Left));
+    }
+
+    #[test]
+    fn cached_search_values_match_recomputed_ones() {
+        let state = game_state(
+            7,
+            7,
+            vec![snake("us", &[(2, 3), (2, 2), (2, 1)]), snake("them", &[(4, 4), (4, 5), (5, 5)])],
+            &[(3, 5), (0, 0)],
+        );
+        let config = fixed_depth(3);
+        let memory = GameMemory::default();
+        let terms = PositionTerms::new(&state, &config, &memory);
+        let opponent_ids = ["them"];
+        let value = |table: &RefCell<HashMap<u64, TableEntry>>, depth| {
+            let ctx = SearchContext {
+                you_id: "us",
+                opponent_ids: &opponent_ids,
+                config: &config,
+                terms: &terms,
+                root_depth: depth,
+                deadline: no_deadline(),
+                timed_out: Cell::new(false),
+                table,
+            };
+            best_reply_value(&state.board, &ctx, 0, depth)
+        };
+
+        let shared = RefCell::new(HashMap::new());
+        for depth in 1..=3 {
+            let computed = value(&RefCell::new(HashMap::new()), depth);
+            // Filled by this call, then answered from the table on the next
+            assert_eq!(value(&shared, depth), computed, "depth {depth}");
+            let root = shared.borrow()[&position_hash(&state.board, 0)];
+            assert_eq!((root.depth, root.value), (depth, computed));
+            assert_eq!(value(&shared, depth), computed, "depth {depth}");
+        }
+
+        // The key doesn't depend on the order the snakes are listed in
+        let mut reordered = state.board.clone();
+        reordered.snakes.reverse();
+        assert_eq!(position_hash(&reordered, 0), position_hash(&state.board, 0));
+        assert_ne!(position_hash(&state.board, 1), position_hash(&state.board, 0));
+    }
+
+    #[test]
+    fn search_models_only_the_nearest_opponents() {
+        let board_with = |near_a: Option<Snake>, far: Snake| {
+            let mut snakes = vec![snake("us", &[(5, 5), (5, 4), (5, 3), (5, 2)])];
+            snakes.extend(near_a);
+            snakes.push(snake("b", &[(3, 6), (2, 6), (1, 6)]));
+            snakes.push(far);
+            game_state(15, 15, snakes, &[])
+        };
+        let near_a = snake("a", &[(7, 6), (8, 6), (9, 6)]);
+        let far = snake("far", &[(14, 14), (14, 13), (14, 12)]);
+        let config = Config { search_opponents: 2, ..fixed_depth(1) };
+        let scores = |state: &GameState| {
+            let decision = bilinear_duel(state, &config, &GameMemory::default(), no_deadline());
+            decision.scores.iter().map(|m| m.score).collect::<Vec<_>>()
+        };
+
+        let state = board_with(Some(near_a.clone()), far.clone());
+        let modeled: Vec<&str> = nearest_opponents(&state.board, &state.you, config.search_opponents, &config)
+            .iter()
+            .map(|snake| snake.id.as_str())
+            .collect();
+        assert_eq!(modeled, ["a", "b"]);
+        let baseline = scores(&state);
+        // The far snake doing something else changes nothing
+        let elsewhere = snake("far", &[(14, 0), (14, 1), (14, 2)]);
+        assert_eq!(scores(&board_with(Some(near_a), elsewhere)), baseline);
+        // A near one disappearing does
+        assert_ne!(scores(&board_with(None, far)), baseline);
+    }
+
+    #[test]
+    fn head_to_head_margin_boundary() {
+        let board = game_state(11, 11, vec![snake("us", &[(5, 5)])], &[]).board;
+        let us = |length: i32| snake("us", &(0..length).map(|y| (5, y)).collect::<Vec<_>>());
+        let them = snake("them", &[(8, 8), (8, 9), (8, 10)]);
+        let config = Config::default();
+        assert_eq!(config.head_to_head_margin, 1);
+        assert!(config.wins_head_on(&us(4), &them, &board));
+        assert!(!config.wins_head_on(&us(3), &them, &board), "equal length is not a win");
+
+        let cautious = Config { head_to_head_margin: 2, ..Config::default() };
+        assert!(cautious.wins_head_on(&us(5), &them, &board));
+        assert!(!cautious.wins_head_on(&us(4), &them, &board));
+    }
+
+    #[actix_web::test]
+    async fn fallback_turns_save_their_payload() {
+        let path = std::env::temp_dir().join(format!("fallbacks-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // The margin eats the whole engine timeout: the search can only time out
+        let config = Config {
+            latency_margin_ms: 500,
+            fallback_log: Some(path.to_string_lossy().into_owned()),
+            ..fixed_depth(2)
+        };
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(AppState::default()))
+                .route("/move", web::post().to(r#move)),
+        )
+        .await;
+        let mut state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), snake("them", &[(1, 1), (1, 0)])], &[]);
+        state.game.id = "fallback-game".to_string();
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+
+        let saved = std::fs::read_to_string(&path).expect("payload written");
+        let _ = std::fs::remove_file(&path);
+        let line: serde_json::Value = serde_json::from_str(saved.lines().next().expect("one line")).unwrap();
+        assert_eq!(line["state"]["game"]["id"], "fallback-game");
+        assert_eq!(line["move"], body["move"]);
+    }
+
+    #[test]
+    fn food_access_rewards_keeping_food_in_reach() {
+        // Left seals us into column 0, right stays in the region with the food
+        let mut you = snake("us", &[(1, 3), (1, 2), (1, 1), (1, 0), (2, 0), (3, 0)]);
+        you.health = 50;
+        let state = game_state(7, 7, vec![you, snake("wall", &[(2, 5), (2, 4), (1, 4), (0, 4), (0, 5)])], &[(5, 5)]);
+        let config = Config::default();
+        let access = |dir, you: &Snake| {
+            let (board, moved) = step_onto(&get_new_position(&you.body[0], dir), you, &state.board);
+            food_access(&moved.body[0], &board, &moved, &config)
+        };
+        assert_eq!(access(Direction::Left, &state.you), -config.weights.food_access);
+        assert_eq!(access(Direction::Right, &state.you), config.weights.food_access / 2.0);
+
+        // Above the health threshold it stays out of the way
+        let mut healthy = state.you.clone();
+        healthy.health = config.food_access_health;
+        assert_eq!(access(Direction::Left, &healthy), 0.0);
+    }
+
+    #[test]
+    fn food_free_boards_leave_the_food_terms_at_zero() {
+        let mut you = snake("us", &[(2, 5), (2, 4), (2, 3), (2, 2)]);
+        you.health = 30;
+        let state = game_state(11, 11, vec![you, snake("them", &[(6, 6), (7, 6), (8, 6), (9, 6), (10, 6)])], &[]);
+        let config = fixed_depth(1);
+        let no_food = Config {
+            weights: Weights { food: 0.0, food_access: 0.0, region_food: 0.0, ..Weights::default() },
+            ..fixed_depth(1)
+        };
+        let memory = GameMemory::default();
+        let terms = PositionTerms::new(&state, &config, &memory);
+        assert_eq!(terms.hunger, Hunger::Fed);
+
+        let scores = |config: &Config| {
+            let terms = PositionTerms::new(&state, config, &memory);
+            let moves = Direction::all().into_iter().map(Move::new).collect();
+            evaluate_moves(moves, &state, &terms).iter().map(|m| m.score).collect::<Vec<_>>()
+        };
+        assert_eq!(scores(&config), scores(&no_food));
+        let decide = |config: &Config| bilinear_duel(&state, config, &memory, no_deadline()).direction;
+        assert_eq!(decide(&config), decide(&no_food));
+    }
+
+    #[actix_web::test]
+    async fn self_check_logs_the_active_games() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app_state = web::Data::new(AppState::default());
+        app_state.games.lock().unwrap().insert("never-ended".to_string(), GameMemory::default());
+        let task = actix_web::rt::spawn(self_check(app_state.clone(), Duration::from_millis(10)));
+        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+        task.abort();
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let check = output.lines().find(|line| line.contains("self check")).expect("self check ran");
+        assert!(check.contains("active_games=1"), "{check}");
+    }
+
+    #[test]
+    fn the_shortest_snake_chases_food_even_when_healthy() {
+        let food_contribution = |ours: usize, theirs: usize| {
+            let us: Vec<(i32, i32)> = (0..ours as i32).map(|y| (5, 5 - y)).collect();
+            let them: Vec<(i32, i32)> = (0..theirs as i32).map(|x| (10 - x, 10)).collect();
+            let state = game_state(11, 11, vec![snake("us", &us), snake("them", &them)], &[(5, 8)]);
+            let memory = GameMemory::default();
+            let up_score = |config: &Config| {
+                let terms = PositionTerms::new(&state, config, &memory);
+                assert_eq!(terms.hunger, Hunger::Fed);
+                evaluate_moves(vec![Move::new(Direction::Up)], &state, &terms)[0].score
+            };
+            let no_food = Config { weights: Weights { food: 0.0, ..Weights::default() }, ..Config::default() };
+            up_score(&Config::default()) - up_score(&no_food)
+        };
+        let shortest = food_contribution(3, 5);
+        let longest = food_contribution(5, 3);
+        assert!((shortest / longest - SHORTEST_FOOD_DRIVE / LONGEST_FOOD_DRIVE).abs() < 1e-9);
+        assert!(shortest > longest);
+    }
+
+    #[actix_web::test]
+    async fn move_latency_is_reported_and_recorded() {
+        let config = Config { debug: true, ..fixed_depth(1) };
+        let app_state = web::Data::new(AppState::default());
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                .app_data(web::Data::new(config))
+                .app_data(app_state.clone())
+                .route("/move", web::post().to(r#move)),
+        )
+        .await;
+        let state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[(8, 8)]);
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        let body: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+
+        let reported = body["debug"]["latency_ms"].as_f64().expect("latency in the debug object");
+        assert!(reported >= 0.0);
+        let metrics = app_state.metrics.lock().unwrap().clone();
+        assert_eq!(metrics.moves, 1);
+        assert_eq!(metrics.total_latency_ms, metrics.max_latency_ms);
+        assert!((metrics.total_latency_ms - reported).abs() < 1e-9);
+    }
+
+    #[test]
+    fn freshly_spawned_snakes_move_sanely() {
+        let spawned = snake("us", &[(5, 5), (5, 5), (5, 5)]);
+        assert_eq!(reverse_direction(&spawned), None);
+        assert!(!tail_will_move(&spawned));
+        assert!(tail_will_move(&snake("us", &[(5, 5)])));
+
+        let state = game_state(11, 11, vec![spawned.clone(), snake("them", &[(1, 1), (1, 1), (1, 1)])], &[(8, 5)]);
+        assert_eq!(safe_moves(&spawned, &state.board).len(), 4);
+        let decision = explain_decision(&state, &fixed_depth(2), &GameMemory::default());
+        assert!(!decision.is_fallback(), "{}", decision.reason);
+        assert!(safe_moves(&spawned, &state.board).contains(&decision.direction));
+
+        // A single-segment snake in the corner still finds its two exits
+        let lone = snake("us", &[(0, 0)]);
+        let state = game_state(11, 11, vec![lone.clone()], &[]);
+        let decision = explain_decision(&state, &fixed_depth(2), &GameMemory::default());
+        assert!(matches!(decision.direction, Direction::Up | Direction::Right));
+    }
+
+    #[test]
+    fn taking_the_dumbbell_corridor_pays_when_the_far_room_is_larger() {
+        // Two rooms joined by the single corridor cell (4, 2); the wall snake's
+        // head is in the small left room with us, and its tail just grew
+        let wall = snake("wall", &[(0, 4), (4, 4), (4, 3), (4, 1), (4, 0), (4, 0)]);
+        let state = game_state(11, 5, vec![snake("us", &[(3, 2), (2, 2), (1, 2)]), wall], &[]);
+        let config = Config::default();
+        let mut moves: Vec<Move> = [Direction::Up, Direction::Down, Direction::Right].into_iter().map(Move::new).collect();
+        reward_chokepoint(&mut moves, &state.board, &state.you, &config);
+        assert_eq!((moves[0].score, moves[1].score), (0.0, 0.0));
+        assert!(moves[2].score > 0.0);
+
+        // With the rooms the other way round there is nothing to gain
+        let mirrored = snake("wall", &[(10, 4), (4, 4), (4, 3), (4, 1), (4, 0), (4, 0)]);
+        let state = game_state(11, 5, vec![snake("us", &[(5, 2), (6, 2), (7, 2)]), mirrored], &[]);
+        let mut moves = vec![Move::new(Direction::Left)];
+        reward_chokepoint(&mut moves, &state.board, &state.you, &config);
+        assert_eq!(moves[0].score, 0.0);
+    }
+
+    #[test]
+    fn each_fallback_strategy_follows_its_rule() {
+        let pick = |state: &GameState, fallback_strategy| fallback_move(state, &Config { fallback_strategy, ..Config::default() });
+
+        // Heading right across the upper right of an open board
+        let open = game_state(11, 11, vec![snake("us", &[(7, 8), (6, 8), (5, 8)])], &[]);
+        assert_eq!(pick(&open, FallbackStrategy::FirstSafe), Direction::Up);
+        assert_eq!(pick(&open, FallbackStrategy::Straight), Direction::Right);
+        assert_eq!(pick(&open, FallbackStrategy::Center), Direction::Down);
+
+        // Left is first in order but runs into the four cells of column 0
+        let pocket = game_state(
+            7,
+            7,
+            vec![snake("us", &[(1, 3), (1, 2), (1, 1), (1, 0), (2, 0), (3, 0)]), snake("wall", &[(2, 5), (2, 4), (1, 4), (0, 4), (0, 5)])],
+            &[],
+        );
+        assert_eq!(pick(&pocket, FallbackStrategy::FirstSafe), Direction::Left);
+        assert_eq!(pick(&pocket, FallbackStrategy::LargestSpace), Direction::Right);
+        // Straight on is the wall, so it settles for the first safe move
+        assert_eq!(pick(&pocket, FallbackStrategy::Straight), Direction::Left);
+    }
+
+    #[test]
+    fn hazard_cells_count_fractionally_toward_space() {
+        let mut state = game_state(5, 5, vec![snake("us", &[(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 4)])], &[]);
+        state.board.hazards = coords(&[(4, 0), (4, 1), (4, 2), (4, 3), (4, 4)]);
+        let config = Config::default();
+        let (full, usable, _) = estimate_space(&state.board, &Coord { x: 2, y: 2 }, &config);
+        // The snake holds column 0, leaving 20 cells of which 5 are hazard
+        assert_eq!(full, reachable_space(&state.board, &Coord { x: 2, y: 2 }, &config));
+        assert_eq!(full, 20);
+        assert!((usable - (15.0 + 5.0 * config.hazard_space_weight)).abs() < 1e-9);
+
+        let (_, undiscounted, _) = estimate_space(&state.board, &Coord { x: 2, y: 2 }, &Config { hazard_space_weight: 1.0, ..Config::default() });
+        assert!((undiscounted - full as f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn trapped_opponents_mean_a_certain_win() {
+        // Their head in the corner is walled in by its own neck and stacked tail
+        let them = snake("them", &[(0, 6), (0, 5), (1, 5), (1, 6), (1, 6)]);
+        let state = game_state(7, 7, vec![snake("us", &[(4, 2), (4, 1), (4, 0)]), them.clone()], &[]);
+        assert!(safe_moves(&them, &state.board).is_empty());
+        let decision = explain_decision(&state, &fixed_depth(2), &GameMemory::default());
+        assert_eq!(decision.reason, "certain win");
+        let safe = safe_moves(&state.you, &state.board);
+        assert_eq!(Some(decision.direction), certain_win_move(&safe, &state.you, &state.board, &Config::default()));
+
+        // Down to one safe move, that move is still taken
+        let cornered = game_state(7, 7, vec![snake("us", &[(6, 0), (6, 1), (6, 2), (5, 2), (5, 1), (5, 1)]), them], &[]);
+        let decision = explain_decision(&cornered, &fixed_depth(2), &GameMemory::default());
+        assert_eq!(decision.direction, Direction::Left);
+
+        // With a way out for them, the search plays on
+        let free = game_state(7, 7, vec![snake("us", &[(4, 2), (4, 1), (4, 0)]), snake("them", &[(0, 6), (0, 5), (0, 4)])], &[]);
+        assert_ne!(explain_decision(&free, &fixed_depth(2), &GameMemory::default()).reason, "certain win");
+    }
+
+    #[test]
+    fn tournament_games_use_the_cautious_preset() {
+        let config = Config::from_toml(
+            "personality = \"aggressive\"\n\
+             [source_presets.tournament]\n\
+             personality = \"balanced\"\n\
+             weights = { threat = 5000.0 }\n",
+        )
+        .expect("valid config");
+        let payload = serde_json::json!({
+            "id": "g", "ruleset": { "name": "standard", "version": "v1" }, "timeout": 500, "source": "tournament"
+        });
+        let game: Game = serde_json::from_value(payload).expect("valid game");
+        assert_eq!(game.source, "tournament");
+
+        let cautious = config.for_source(&game.source).expect("a tournament preset");
+        assert_eq!(cautious.personality, Personality::Balanced);
+        assert_eq!(cautious.weights.threat, 5000.0);
+        // Unlisted weights are back at their defaults
+        assert_eq!(cautious.weights.food, Weights::default().food);
+        assert!(config.for_source("ladder").is_none());
+
+        // Without a source the field defaults to empty, which has no preset
+        let bare: Game = serde_json::from_value(serde_json::json!({ "id": "g", "ruleset": { "name": "standard", "version": "v1" }, "timeout": 500 }))
+            .expect("valid game");
+        assert!(config.for_source(&bare.source).is_none());
+    }
+
+    #[test]
+    fn eating_in_the_lookahead_keeps_the_tail_in_place() {
+        // Stepping down puts our head right above the cell our tail will then hold
+        let body = [(2, 3), (1, 3), (1, 2), (1, 1), (2, 1), (3, 1)];
+        let hungry = game_state(7, 7, vec![Snake { health: 40, ..snake("us", &body) }], &[]);
+        let after = simulate_turn(&hungry.board, &[("us", Direction::Down)]);
+        assert!(safe_moves(&after.snakes[0], &after).contains(&Direction::Down));
+
+        let fed = game_state(7, 7, vec![Snake { health: 40, ..snake("us", &body) }], &[(2, 2)]);
+        let after = simulate_turn(&fed.board, &[("us", Direction::Down)]);
+        let us = &after.snakes[0];
+        assert_eq!(us.health, 100);
+        assert_eq!(us.body.len(), body.len() + 1);
+        assert!(after.food.is_empty());
+        // The stacked tail stays for a turn, so the next ply can't follow it
+        assert!(!safe_moves(us, &after).contains(&Direction::Down));
+    }
+
+    #[actix_web::test]
+    async fn posted_weights_steer_the_next_move() {
+        let config = Config { debug: true, ..fixed_depth(1) };
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(AppState::default()))
+                .route("/move", web::post().to(r#move))
+                .route("/debug/weights", web::post().to(debug_weights)),
+        )
+        .await;
+        // Heading down from above the center, with food off to the left
+        let state = game_state(11, 11, vec![snake("us", &[(5, 7), (5, 8), (5, 9)])], &[(0, 7)]);
+        let play = |weights: Weights| {
+            let app = &app;
+            let state = &state;
+            async move {
+                let request = actix_web::test::TestRequest::post().uri("/debug/weights").set_json(&weights).to_request();
+                assert!(actix_web::test::call_service(app, request).await.status().is_success());
+                let request = actix_web::test::TestRequest::post().uri("/move").set_json(state).to_request();
+                let response: serde_json::Value = actix_web::test::call_and_read_body_json(app, request).await;
+                response["move"].as_str().expect("a move").to_string()
+            }
+        };
+
+        let hungry = Weights { food: 1000.0, center: 0.0, ..Weights::default() };
+        assert_eq!(play(hungry).await, "left");
+        let central = Weights { food: 0.0, center: 1000.0, center_decay: 0.0, ..Weights::default() };
+        assert_eq!(play(central).await, "down");
+    }
+
+    #[test]
+    fn cornered_opponents_pose_no_threat() {
+        let config = Config::default();
+        let us = snake("us", &[(3, 4), (3, 3), (3, 2)]);
+        // Two cells from their head, a longer snake could meet us there next turn
+        let target = Coord { x: 2, y: 6 };
+
+        let free = game_state(7, 7, vec![us.clone(), snake("them", &[(0, 6), (0, 5), (0, 4), (0, 3), (0, 2)])], &[]);
+        assert!(evaluate_threats(&target, &free.board, &us, &config) < 0.0);
+
+        // The same snake curled into the corner, boxed in by its neck and stacked tail
+        let cornered = game_state(7, 7, vec![us.clone(), snake("them", &[(0, 6), (0, 5), (1, 5), (1, 6), (1, 6)])], &[]);
+        assert!(safe_moves(&cornered.board.snakes[1], &cornered.board).is_empty());
+        assert_eq!(evaluate_threats(&target, &cornered.board, &us, &config), 0.0);
+    }
+
+    #[actix_web::test]
+    async fn offloaded_moves_match_inline_ones() {
+        let mut state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), snake("them", &[(8, 8), (8, 7), (8, 6)])], &[(2, 5)]);
+        let mut answers = Vec::new();
+        for offload_compute in [false, true] {
+            let config = Config { offload_compute, ..fixed_depth(2) };
+            let app = actix_web::test::init_service(
+                App::new()
+                    .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                    .app_data(web::Data::new(config))
+                    .app_data(web::Data::new(AppState::default()))
+                    .route("/move", web::post().to(r#move)),
+            )
+            .await;
+            let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+            let response: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+            answers.push(response["move"].clone());
+        }
+        assert_eq!(answers[0], answers[1]);
+
+        // Hemmed in by the wall and our own body, the only way out is up
+        state.board.snakes[0] = snake("us", &[(0, 0), (1, 0), (2, 0)]);
+        state.you = state.board.snakes[0].clone();
+        let config = Config { offload_compute: true, ..fixed_depth(2) };
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(AppState::default()))
+                .route("/move", web::post().to(r#move)),
+        )
+        .await;
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        let response: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+        assert_eq!(response["move"], "up");
+    }
+
+    #[test]
+    fn edge_cells_cost_more_with_opponents_nearby() {
+        let config = Config::default();
+        let us = snake("us", &[(5, 1), (5, 2), (5, 3)]);
+        let edge = Coord { x: 5, y: 0 };
+        let alone = game_state(11, 11, vec![us.clone()], &[]);
+        assert_eq!(edge_crowding(&edge, &alone.board, &us, &config), 0.0);
+
+        let crowded = game_state(
+            11,
+            11,
+            vec![us.clone(), snake("a", &[(3, 1), (2, 1)]), snake("b", &[(7, 1), (8, 1)]), snake("c", &[(6, 2), (7, 2)])],
+            &[],
+        );
+        let penalty = edge_crowding(&edge, &crowded.board, &us, &config);
+        assert_eq!(penalty, -3.0 * config.weights.edge_crowding);
+        // The cell just inside the edge is not penalized however crowded it is
+        assert_eq!(edge_crowding(&Coord { x: 5, y: 1 }, &crowded.board, &us, &config), 0.0);
+        // Heads beyond CROWDING_RADIUS don't count
+        let tight = Config { crowding_radius: 1, ..Config::default() };
+        assert_eq!(edge_crowding(&edge, &crowded.board, &us, &tight), 0.0);
+    }
+
+    #[test]
+    fn mirroring_keeps_our_offset_to_the_opponent() {
+        // We hold the larger half of the board; they are heading up
+        let them = snake("them", &[(8, 5), (8, 4), (8, 3)]);
+        let state = game_state(11, 11, vec![snake("us", &[(3, 5), (2, 5), (1, 5)]), them], &[]);
+        let scored = |config: &Config, board: &Board| {
+            let mut moves: Vec<Move> = [Direction::Up, Direction::Down, Direction::Right].into_iter().map(Move::new).collect();
+            prefer_mirroring(&mut moves, board, &state.you, config);
+            moves.iter().map(|m| m.score).collect::<Vec<_>>()
+        };
+
+        let mirroring = Config { mirror_opponent: true, ..Config::default() };
+        assert_eq!(scored(&mirroring, &state.board), vec![mirroring.weights.mirror, 0.0, 0.0]);
+        assert_eq!(scored(&Config::default(), &state.board), vec![0.0, 0.0, 0.0]);
+
+        // Only ever heads-up
+        let mut crowded = state.board.clone();
+        crowded.snakes.push(snake("third", &[(5, 9), (5, 10)]));
+        assert_eq!(scored(&mirroring, &crowded), vec![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn flexibility_looks_past_the_immediate_exits() {
+        // Left and right both leave two safe moves, but left's lead into dead ends
+        let state = game_state(
+            5,
+            7,
+            vec![
+                snake("us", &[(3, 3), (3, 2), (3, 1), (3, 0)]),
+                snake("them", &[(2, 5), (1, 5), (1, 4), (1, 3), (1, 2), (1, 1), (2, 1), (2, 0)]),
+            ],
+            &[],
+        );
+        let after = |dir| step_onto(&get_new_position(&state.you.body[0], dir), &state.you, &state.board);
+        let (left, moved_left) = after(Direction::Left);
+        let (right, moved_right) = after(Direction::Right);
+        assert_eq!(safe_moves(&moved_left, &left).len(), safe_moves(&moved_right, &right).len());
+        let flexibility_right = future_flexibility(&right, &moved_right, FLEXIBILITY_DEPTH);
+        assert!(flexibility_right > future_flexibility(&left, &moved_left, FLEXIBILITY_DEPTH));
+    }
+
+    #[test]
+    fn directions_serialize_to_the_engine_strings() {
+        let expected = [(Direction::Up, "up"), (Direction::Down, "down"), (Direction::Left, "left"), (Direction::Right, "right")];
+        for (direction, name) in expected {
+            assert_eq!(serde_json::to_value(direction).unwrap(), name);
+            assert_eq!(direction.as_str(), name);
+            let response = serde_json::to_value(MoveResponse { r#move: direction, debug: None }).unwrap();
+            assert_eq!(response, serde_json::json!({ "move": name }));
+        }
+    }
+
+    #[test]
+    fn an_empty_snake_list_gets_a_safe_default() {
+        // We sit in the corner facing the wall, so only right is open
+        let mut state = game_state(7, 7, vec![snake("us", &[(0, 6), (0, 5), (0, 4)])], &[(3, 3)]);
+        state.board.snakes.clear();
+        let decision = explain_decision(&state, &fixed_depth(2), &GameMemory::default());
+        assert_eq!((decision.direction, decision.reason), (Direction::Right, "no snakes"));
+
+        // Helpers that look at the snakes cope with none at all
+        let config = Config::default();
+        assert!(nearest_opponents(&state.board, &state.you, 3, &config).is_empty());
+        assert_eq!(reachable_space(&state.board, &Coord { x: 3, y: 3 }, &config), 49);
+        assert_eq!(fallback_move(&state, &config), Direction::Right);
+    }
+
+    #[test]
+    fn standing_on_a_hungry_opponents_food_path_is_rewarded() {
+        let mut hungry = snake("them", &[(0, 5), (0, 6), (0, 7)]);
+        hungry.health = 10;
+        let state = game_state(9, 9, vec![snake("us", &[(2, 4), (2, 3), (2, 2)]), hungry], &[(4, 5)]);
+        let config = Config { personality: Personality::Aggressive, ..Config::default() };
+        let denial = |dir| {
+            let (after, moved) = step_onto(&get_new_position(&state.you.body[0], dir), &state.you, &state.board);
+            food_denial_score(&after, &moved, &config)
+        };
+        assert!(denial(Direction::Up) > 0.0);
+        assert_eq!(denial(Direction::Right), 0.0);
+    }
+
+    #[test]
+    fn head_to_heads_count_the_meal_next_to_their_head() {
+        let you = snake("us", &[(3, 5), (2, 5), (1, 5), (0, 5)]);
+        let state = game_state(11, 11, vec![you.clone(), snake("them", &[(5, 5), (6, 5), (7, 5)])], &[(5, 6)]);
+        let them = &state.board.snakes[1];
+        let ignoring = Config { head_to_head_growth: false, ..fixed_depth(1) };
+        let config = fixed_depth(1);
+        assert!(ignoring.wins_head_on(&you, them, &state.board));
+        assert!(!config.wins_head_on(&you, them, &state.board));
+
+        let contested = Coord { x: 4, y: 5 };
+        assert!(evaluate_threats(&contested, &state.board, &you, &ignoring) > 0.0);
+        assert!(evaluate_threats(&contested, &state.board, &you, &config) < 0.0);
+        let decision = bilinear_duel(&state, &config, &GameMemory::default(), no_deadline());
+        assert_ne!(decision.direction, Direction::Right);
+    }
+
+    #[test]
+    fn only_the_nearest_opponents_are_modeled() {
+        let us = snake("us", &[(7, 7), (7, 6), (7, 5)]);
+        let snakes = vec![
+            us.clone(),
+            // Three longer snakes whose heads are two cells from (7, 8)
+            snake("north", &[(7, 10), (7, 11), (7, 12), (7, 13)]),
+            snake("west", &[(5, 8), (4, 8), (3, 8), (2, 8)]),
+            snake("east", &[(9, 8), (10, 8), (11, 8), (12, 8)]),
+            // Four more out in the corners
+            snake("a", &[(0, 0), (1, 0), (2, 0), (3, 0)]),
+            snake("b", &[(14, 14), (13, 14), (12, 14), (11, 14)]),
+            snake("c", &[(0, 14), (0, 13), (0, 12), (0, 11)]),
+            snake("d", &[(14, 0), (14, 1), (14, 2), (14, 3)]),
+        ];
+        let state = game_state(15, 15, snakes, &[]);
+        let pos = Coord { x: 7, y: 8 };
+        let threat = |max_modeled_opponents| {
+            let config = Config { max_modeled_opponents, ..Config::default() };
+            evaluate_threats(&pos, &state.board, &us, &config)
+        };
+
+        let all = threat(None);
+        assert!(all < 0.0);
+        // The three nearest carry the whole threat; the corners never mattered
+        assert_eq!(threat(Some(3)), all);
+        assert_eq!(threat(Some(1)), all / 3.0);
+        assert_eq!(threat(Some(0)), 0.0);
+        let nearest = nearest_opponents(&state.board, &us, 3, &Config::default());
+        assert!(nearest.iter().all(|snake| ["north", "west", "east"].contains(&snake.id.as_str())));
+    }
+
+    #[test]
+    fn a_length_two_snake_never_reverses() {
+        let us = snake("us", &[(3, 3), (3, 2)]);
+        let state = game_state(7, 7, vec![us.clone()], &[]);
+        assert_eq!(reverse_direction(&us), Some(Direction::Down));
+        // The neck is also the tail, which moves out of the way, so the cell alone looks open
+        assert!(tail_will_move(&us));
+        assert!(is_move_safe(&Coord { x: 3, y: 2 }, &state.board));
+        assert_eq!(safe_moves(&us, &state.board), vec![Direction::Up, Direction::Left, Direction::Right]);
+
+        let decision = explain_decision(&state, &fixed_depth(2), &GameMemory::default());
+        assert_ne!(decision.direction, Direction::Down);
+        assert!(decision.scores.iter().all(|m| m.direction != Direction::Down || !m.score.is_finite()));
+        // Nor does the lookahead let it turn back
+        let next = simulate_turn(&state.board, &[("us", Direction::Down)]);
+        assert!(next.snakes.is_empty());
+    }
+
+    #[test]
+    fn slow_decisions_stop_at_the_hard_cap() {
+        static EVALUATIONS: AtomicUsize = AtomicUsize::new(0);
+        // Each move takes longer to evaluate than half the cap
+        fn slow_evaluation() {
+            EVALUATIONS.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(60));
+        }
+        let state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[]);
+        let config = Config { hard_move_cap_ms: 100, ..fixed_depth(4) };
+
+        EVALUATION_HOOK.with(|hook| hook.set(Some(slow_evaluation)));
+        let decision = explain_decision(&state, &config, &GameMemory::default());
+        EVALUATION_HOOK.with(|hook| hook.set(None));
+
+        // The third move would start past the deadline, so it is never evaluated
+        assert!(EVALUATIONS.load(Ordering::SeqCst) < 3);
+        assert_eq!(decision.reason, "search timeout");
+        assert!(safe_moves(&state.you, &state.board).contains(&decision.direction));
+    }
+
+    #[test]
+    fn food_in_the_reachable_region_adds_to_its_score() {
+        // Our body and a stub below it split the board into two 5x5 rooms,
+        // with food only in the left one
+        let state = game_state(
+            11,
+            5,
+            vec![snake("us", &[(5, 2), (5, 3), (5, 4), (5, 4), (5, 4)]), snake("stub", &[(5, 0), (5, 1), (5, 1)])],
+            &[(2, 2)],
+        );
+        let memory = GameMemory::default();
+        let margin = |region_food| {
+            let config = Config {
+                weights: Weights { food: 0.0, food_access: 0.0, food_denial: 0.0, region_food, ..Weights::default() },
+                ..fixed_depth(1)
+            };
+            let terms = PositionTerms::new(&state, &config, &memory);
+            let moves = evaluate_moves(vec![Move::new(Direction::Left), Move::new(Direction::Right)], &state, &terms);
+            moves[0].score - moves[1].score
+        };
+        // The rooms are the same size, so the food is all that separates the weighted margins
+        assert!((margin(3.0) - margin(0.0) - 3.0).abs() < 1e-9);
+        assert!(margin(100.0) > 0.0);
+    }
+
+    #[test]
+    fn conservative_tails_avoid_an_ambiguous_opponent_tail() {
+        // Straight up is the tail of a snake at full health: it may have just eaten
+        let them = Snake { health: 100, ..snake("them", &[(5, 4), (4, 4), (3, 4)]) };
+        let state = game_state(11, 11, vec![snake("us", &[(3, 3), (3, 2), (3, 1)]), them], &[]);
+        let tail = Coord { x: 3, y: 4 };
+        assert!(ambiguous_tail(&tail, &state.board, &state.you));
+        let memory = GameMemory::default();
+        let up_score = |state: &GameState, conservative_tails| {
+            let config = Config { conservative_tails, ..fixed_depth(1) };
+            let terms = PositionTerms::new(state, &config, &memory);
+            evaluate_moves(vec![Move::new(Direction::Up)], state, &terms)[0].score
+        };
+        assert!(up_score(&state, false).is_finite());
+        assert_eq!(up_score(&state, true), f64::NEG_INFINITY);
+
+        // Below full health it can't have just eaten, so its tail is sure to move
+        let mut hungry = state.clone();
+        hungry.board.snakes[1].health = 90;
+        assert!(!ambiguous_tail(&tail, &hungry.board, &hungry.you));
+        assert!(up_score(&hungry, true).is_finite());
+    }
+
+    #[test]
+    fn maximize_length_heads_for_reachable_food() {
+        let config = Config { personality: Personality::MaximizeLength, ..fixed_depth(2) };
+        let memory = GameMemory::default();
+        let them = snake("them", &[(9, 1), (9, 2), (9, 3), (9, 4)]);
+        // Food off in each direction from a healthy snake in the open
+        for food in [(5, 9), (1, 5), (9, 7), (1, 8)] {
+            let state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), them.clone()], &[food]);
+            let decision = explain_decision(&state, &config, &memory);
+            let (head, food) = (&state.you.body[0], Coord { x: food.0, y: food.1 });
+            let step = get_new_position(head, decision.direction);
+            assert!(manhattan_distance(&step, &food) < manhattan_distance(head, &food), "food at {food:?}");
+        }
+
+        // Growing step by step: each pickup retargets the next pellet
+        let mut state = game_state(11, 11, vec![snake("us", &[(2, 5), (2, 4), (2, 3)]), them], &[(4, 5), (6, 5), (8, 5)]);
+        for _ in 0..6 {
+            let direction = explain_decision(&state, &config, &memory).direction;
+            state.board = simulate_turn(&state.board, &[("us", direction)]);
+            state.you = state.board.snakes.iter().find(|s| s.id == "us").expect("still alive").clone();
+        }
+        assert!(state.board.food.is_empty());
+        assert_eq!(state.you.body.len(), 6);
+    }
+
+    #[test]
+    fn oversized_boards_are_handled_exactly() {
+        // Far more cells than any fixed-width occupancy mask would hold
+        let (width, height) = (80, 80);
+        let wall: Vec<(i32, i32)> = (0..height).map(|y| (40, y)).chain([(40, height - 1)]).collect();
+        let state = game_state(width, height, vec![snake("us", &[(70, 70), (70, 69), (70, 68)]), snake("wall", &wall)], &[]);
+        let config = Config::default();
+        // The wall splits off columns 0..40 on the left; on the right our head
+        // and neck are blocked while the tail moves on
+        assert_eq!(reachable_space(&state.board, &Coord { x: 10, y: 10 }, &config), 40 * height);
+        assert_eq!(reachable_space(&state.board, &Coord { x: 75, y: 10 }, &config), 39 * height - 2);
+        assert!(!is_move_safe(&Coord { x: 40, y: 79 }, &state.board));
+        assert!(is_move_safe(&Coord { x: 79, y: 79 }, &state.board));
+        assert!(!is_move_safe(&Coord { x: 80, y: 0 }, &state.board));
+
+        let decision = explain_decision(&state, &fixed_depth(1), &GameMemory::default());
+        assert!(safe_moves(&state.you, &state.board).contains(&decision.direction));
+    }
+
+    #[test]
+    fn predictable_mode_gives_identical_answers() {
+        let state = game_state(
+            11,
+            11,
+            vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), snake("them", &[(7, 6), (8, 6)]), snake("other", &[(2, 8), (2, 9), (2, 10), (1, 10)])],
+            &[(4, 7), (9, 2)],
+        );
+        // Aggression and mirroring are set but have no say
+        let config = Config { predictable: true, personality: Personality::Aggressive, mirror_opponent: true, ..Config::default() };
+        let memory = GameMemory::default();
+        let first = explain_decision(&state, &config, &memory);
+        assert_eq!(first.reason, "predictable");
+        let safe = safe_moves(&state.you, &state.board);
+        assert_eq!(Some(first.direction), roomiest_move(&safe, &state.you, &state.board, &config));
+        for _ in 0..20 {
+            assert_eq!(explain_decision(&state, &config, &memory).direction, first.direction);
+        }
+    }
+
+    #[test]
+    fn safe_tail_keeps_the_tail_in_reach() {
+        // We and a short wall split the board along row 3: up is the bigger
+        // room, but our tail is down in the smaller one
+        let us = snake("us", &[(3, 3), (4, 3), (5, 3), (6, 3), (6, 2), (6, 1), (6, 0)]);
+        let state = game_state(7, 7, vec![us.clone(), snake("wall", &[(2, 3), (1, 3), (0, 3), (0, 3)])], &[]);
+        let up = get_new_position(&us.body[0], Direction::Up);
+        let down = get_new_position(&us.body[0], Direction::Down);
+        assert!(!tail_reachable(&up, &us, &state.board));
+        assert!(tail_reachable(&down, &us, &state.board));
+
+        let memory = GameMemory::default();
+        let roomiest = explain_decision(&state, &Config { predictable: true, ..Config::default() }, &memory);
+        assert_eq!(roomiest.direction, Direction::Up);
+        let decision = explain_decision(&state, &Config { safe_tail: true, ..Config::default() }, &memory);
+        assert_eq!((decision.direction, decision.reason), (Direction::Down, "safe tail"));
+    }
+
+    #[test]
+    fn shrinking_space_turns_on_the_escape_bias() {
+        let config = Config::default();
+        let mut memory = GameMemory::default();
+        let open = game_state(7, 7, vec![snake("us", &[(3, 1), (3, 0), (2, 0)]), snake("them", &[(6, 6), (5, 6), (4, 6)])], &[]);
+        memory.record_space(reachable_space(&open.board, &open.you.body[0], &config));
+        assert!(!PositionTerms::new(&open, &config, &memory).boxed_in);
+
+        // Next turn a wall of opponent body cuts the board in half
+        let wall: Vec<(i32, i32)> = (0..7).map(|x| (x, 3)).chain([(6, 4)]).collect();
+        let closed = game_state(7, 7, vec![snake("us", &[(3, 2), (3, 1), (3, 0)]), snake("them", &wall)], &[]);
+        let boxed = PositionTerms::new(&closed, &config, &memory);
+        assert!(boxed.boxed_in);
+        let calm = PositionTerms { boxed_in: false, ..PositionTerms::new(&closed, &config, &memory) };
+        assert!(position_score(&closed.board, &closed.you, &boxed).0 > position_score(&closed.board, &closed.you, &calm).0);
+    }
+
+    #[test]
+    fn search_prefers_the_side_with_fewer_hazards() {
+        let mut state = game_state(10, 10, vec![snake("us", &[(5, 1), (5, 0), (6, 0)]), snake("them", &[(9, 9), (9, 8), (9, 7)])], &[]);
+        state.game.ruleset.name = "royale".to_string();
+        state.board.hazards = (0..3).flat_map(|x| (0..10).map(move |y| Coord { x, y })).collect();
+        // Deep enough lines can all end up back on the right, so look one move ahead
+        let mut config = fixed_depth(1);
+        config.weights.hazard_quadrant = 10_000.0;
+        let decision = bilinear_duel(&state, &config, &GameMemory::default(), no_deadline());
+        let score = |dir| decision.scores.iter().find(|m| m.direction == dir).unwrap().score;
+        assert!(score(Direction::Left) < score(Direction::Right));
+    }
+
+    #[test]
+    fn log_sampling_keeps_about_the_configured_fraction() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = Config { log_sample_rate: 0.25, ..fixed_depth(1) };
+        // Starving with the only food out of reach is warned about whether or not the turn is sampled
+        let mut state = game_state(7, 7, vec![snake("us", &[(3, 3), (3, 2), (3, 1)])], &[(0, 6)]);
+        state.you.health = 1;
+        state.board.snakes[0].health = 1;
+        let memory = GameMemory::default();
+        let mut sampler = Rng::new(42);
+        let turns = 200;
+        let mut sampled = 0;
+        for _ in 0..turns {
+            let detailed = sampler.chance(config.log_sample_rate);
+            sampled += detailed as usize;
+            guarded_decision(&state, &config, &memory, detailed);
+        }
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let detailed_turns = output.lines().filter(|line| line.contains("search finished")).count();
+        assert_eq!(detailed_turns, sampled);
+        assert!((30..=70).contains(&detailed_turns), "{detailed_turns} of {turns} turns sampled");
+        assert_eq!(output.lines().filter(|line| line.contains("no food reachable before starving")).count(), turns);
+    }
+
+    #[test]
+    fn spawn_anticipation_only_pulls_while_food_is_short() {
+        let config = Config { anticipate_food_spawns: true, ..Config::default() };
+        let memory = GameMemory::default();
+        let mut state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)])], &[(0, 0)]);
+        let score = |state: &GameState| {
+            let terms = PositionTerms::new(state, &config, &memory);
+            (terms.awaiting_spawns, position_score(&state.board, &state.you, &terms).0)
+        };
+        state.game.ruleset.settings.minimum_food = 1;
+        let (awaiting, enough_food) = score(&state);
+        assert!(!awaiting);
+        state.game.ruleset.settings.minimum_food = 3;
+        let (awaiting, short_of_food) = score(&state);
+        assert!(awaiting);
+        assert!(short_of_food > enough_food);
+    }
+
+    #[test]
+    fn duplicate_food_counts_once() {
+        let board = |food: serde_json::Value| -> Board {
+            serde_json::from_value(serde_json::json!({
+                "height": 7, "width": 7, "food": food, "hazards": [],
+                "snakes": [{ "id": "us", "health": 100, "body": [{ "x": 3, "y": 3 }, { "x": 3, "y": 2 }, { "x": 3, "y": 1 }] }]
+            }))
+            .expect("valid board")
+        };
+        let doubled = board(serde_json::json!([{ "x": 1, "y": 5 }, { "x": 1, "y": 5 }, { "x": 5, "y": 5 }, { "x": 1, "y": 5 }]));
+        let single = board(serde_json::json!([{ "x": 1, "y": 5 }, { "x": 5, "y": 5 }]));
+        assert_eq!(doubled.food, coords(&[(1, 5), (5, 5)]));
+
+        let config = fixed_depth(1);
+        let (_, _, reachable_food) = estimate_space(&doubled, &Coord { x: 3, y: 4 }, &config);
+        assert_eq!(reachable_food, 2);
+
+        let memory = GameMemory::default();
+        let scores = |board: Board| {
+            let state = GameState { board, ..game_state(7, 7, vec![snake("us", &[(3, 3), (3, 2), (3, 1)])], &[]) };
+            let terms = PositionTerms::new(&state, &config, &memory);
+            let moves = Direction::all().into_iter().map(Move::new).collect();
+            evaluate_moves(moves, &state, &terms).iter().map(|m| m.score).collect::<Vec<_>>()
+        };
+        assert_eq!(scores(doubled), scores(single));
+    }
+
+    #[test]
+    fn aggression_waits_for_its_start_turn() {
+        let us = snake("us", &[(5, 5), (4, 5), (3, 5), (2, 5), (1, 5), (0, 5)]);
+        let them = snake("them", &[(9, 1), (9, 0), (10, 0)]);
+        let memory = GameMemory::default();
+        let decide = |turn, personality, aggression_start_turn| {
+            let state = GameState { turn, ..game_state(11, 11, vec![us.clone(), them.clone()], &[]) };
+            let config = Config { personality, aggression_start_turn, ..fixed_depth(2) };
+            let decision = explain_decision(&state, &config, &memory);
+            (decision.direction, decision.scores.iter().map(|m| m.score).collect::<Vec<_>>())
+        };
+
+        // Before turn 50 the aggressive snake plays exactly as a balanced one
+        let early = decide(10, Personality::Aggressive, 50);
+        assert_eq!(early, decide(10, Personality::Balanced, 50));
+        assert_ne!(early, decide(10, Personality::Aggressive, 0));
+        // From turn 50 on it hunts
+        let late = decide(60, Personality::Aggressive, 50);
+        assert_eq!(late, decide(60, Personality::Aggressive, 0));
+        assert_ne!(late, decide(60, Personality::Balanced, 50));
+    }
+
+    #[test]
+    fn maximin_passes_up_a_move_that_can_end_the_game() {
+        // Right opens onto the board but can run into the longer snake's head;
+        // down and left stay in a seven-cell pocket next to its body
+        let state = game_state(
+            7,
+            7,
+            vec![snake("us", &[(1, 3), (1, 4), (0, 4), (0, 5), (0, 6)]), snake("them", &[(3, 3), (3, 2), (2, 2), (2, 1), (2, 0), (3, 0), (3, 0)])],
+            &[],
+        );
+        let config = Config::default();
+        let spaces = |ours| -> Vec<i32> {
+            Direction::all()
+                .into_iter()
+                .map(|theirs| {
+                    let next = simulate_turn(&state.board, &[("us", ours), ("them", theirs)]);
+                    next.snakes.iter().find(|s| s.id == "us").map_or(0, |s| reachable_space(&next, &s.body[0], &config))
+                })
+                .collect()
+        };
+        let mean = |spaces: &[i32]| spaces.iter().sum::<i32>() as f64 / spaces.len() as f64;
+        let (right, down) = (spaces(Direction::Right), spaces(Direction::Down));
+        assert!(mean(&right) > mean(&down));
+        assert_eq!(right.iter().min(), Some(&0));
+        assert!(down.iter().all(|space| *space >= 7));
+
+        let safe = safe_moves(&state.you, &state.board);
+        assert_eq!(maximin_move(&safe, &state.you, &state.board, &config), Some(Direction::Down));
+        let decision = explain_decision(&state, &Config { maximin: true, ..Config::default() }, &GameMemory::default());
+        assert_eq!((decision.direction, decision.reason), (Direction::Down, "maximin"));
+    }
+
+    #[test]
+    fn padded_ids_still_match_our_snake() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = Config::default();
+        let mut state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), snake("them", &[(8, 8), (8, 7), (8, 6)])], &[]);
+        state.you.id = " us\t".to_string();
+        normalize_ids(&mut state, &config);
+        assert_eq!(state.you.id, "us");
+        let opponents = nearest_opponents(&state.board, &state.you, 3, &config);
+        assert_eq!(opponents.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), vec!["them"]);
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("only matched the board after normalizing ids"), "{output}");
+
+        // Casing only counts with CASE_INSENSITIVE_IDS
+        let mut shouted = state.clone();
+        shouted.you.id = "US".to_string();
+        normalize_ids(&mut shouted, &config);
+        assert!(shouted.board.snakes.iter().all(|s| s.id != shouted.you.id));
+        normalize_ids(&mut shouted, &Config { case_insensitive_ids: true, ..Config::default() });
+        assert_eq!(shouted.board.snakes[0].id, "US");
+        assert_eq!(nearest_opponents(&shouted.board, &shouted.you, 3, &config).len(), 1);
+    }
+
+    #[test]
+    fn extended_bodies_score_above_coiled_ones() {
+        let extended = snake("us", &[(8, 10), (7, 10), (6, 10), (5, 10), (4, 10), (3, 10), (2, 10), (1, 10), (0, 10)]);
+        let coiled = snake("us", &[(1, 1), (0, 1), (0, 0), (1, 0), (2, 0), (2, 1), (2, 2), (1, 2), (0, 2)]);
+        assert!(body_spread(&extended) > body_spread(&coiled));
+
+        let mut config = Config::default();
+        config.weights.spine = 1_000.0;
+        let memory = GameMemory::default();
+        let score = |you: Snake| {
+            let state = game_state(19, 19, vec![you], &[]);
+            let terms = PositionTerms::new(&state, &config, &memory);
+            position_score(&state.board, &state.you, &terms).0
+        };
+        assert!(score(extended) > score(coiled));
+    }
+
+    #[actix_web::test]
+    async fn ties_go_to_last_turns_food_target() {
+        // Mirror-image food either side of a snake that has run into the top edge
+        let state = game_state(11, 11, vec![snake("us", &[(5, 10), (5, 9), (5, 8)])], &[(2, 9), (8, 9)]);
+        let config = Config { weights: Weights { center: 0.0, food: 0.0, ..Weights::default() }, ..fixed_depth(1) };
+        let memory = GameMemory::default();
+        // Without the food weight or the off-center middle of an odd board
+        // pulling one way, left and right score the same
+        let terms = PositionTerms::new(&state, &config, &memory);
+        let mut moves = evaluate_moves(Direction::all().into_iter().map(Move::new).collect(), &state, &terms);
+        let score = |moves: &[Move], dir| moves.iter().find(|m| m.direction == dir).map(|m| m.score).unwrap();
+        assert!(score(&moves, Direction::Left).is_finite());
+        assert_eq!(score(&moves, Direction::Left), score(&moves, Direction::Right));
+        // Rounding noise from the search blend still counts as a tie
+        moves.iter_mut().filter(|m| m.direction == Direction::Right).for_each(|m| m.score += TIE_EPSILON / 2.0);
+
+        let chasing = |x| GameMemory { food_target: Some(Coord { x, y: 9 }), ..GameMemory::default() };
+        assert_eq!(keep_to_target(&moves, &chasing(8), &state.board, &state.you), Some(Direction::Right));
+        assert_eq!(keep_to_target(&moves, &chasing(2), &state.board, &state.you), Some(Direction::Left));
+        assert_eq!(keep_to_target(&moves, &GameMemory::default(), &state.board, &state.you), None);
+        // A clear winner is left alone
+        moves.iter_mut().filter(|m| m.direction == Direction::Left).for_each(|m| m.score += 1.0);
+        assert_eq!(keep_to_target(&moves, &chasing(8), &state.board, &state.you), None);
+
+        // The remembered target goes with the game at /end
+        let app_state = web::Data::new(AppState::default());
+        app_state.games.lock().unwrap().insert(state.game.id.clone(), chasing(8));
+        let app = actix_web::test::init_service(App::new().app_data(app_state.clone()).route("/end", web::post().to(end))).await;
+        let request = actix_web::test::TestRequest::post().uri("/end").set_json(&state).to_request();
+        assert!(actix_web::test::call_service(&app, request).await.status().is_success());
+        assert!(app_state.games.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn search_overrides_of_the_greedy_move_are_counted() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        // Greedy takes the open board to the right; the search sees the longer
+        // snake's head can meet us there and stays in the pocket
+        let state = game_state(
+            7,
+            7,
+            vec![snake("us", &[(1, 3), (1, 4), (0, 4), (0, 5), (0, 6)]), snake("them", &[(3, 3), (3, 2), (2, 2), (2, 1), (2, 0), (3, 0), (3, 0)])],
+            &[],
+        );
+        let config = fixed_depth(2);
+        let decision = explain_decision(&state, &config, &GameMemory::default());
+        assert_eq!((decision.greedy, decision.direction), (Some(Direction::Right), Direction::Down));
+
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(AppState::default()))
+                .route("/move", web::post().to(r#move))
+                .route("/metrics", web::get().to(metrics)),
+        )
+        .await;
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        assert!(actix_web::test::call_service(&app, request).await.status().is_success());
+        let request = actix_web::test::TestRequest::get().uri("/metrics").to_request();
+        let counters: serde_json::Value = actix_web::test::call_and_read_body_json(&app, request).await;
+        assert_eq!((counters["moves"].as_u64(), counters["search_overrides"].as_u64()), (Some(1), Some(1)));
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let line = output.lines().find(|line| line.contains("search overrode the greedy move")).expect("override logged");
+        assert!(line.contains("greedy=right") && line.contains("chosen=down"), "{line}");
+        assert!(line.contains("greedy_score=") && line.contains("chosen_score="), "{line}");
+    }
+
+    #[test]
+    fn duel_food_is_ranked_by_the_control_it_gives() {
+        let state = game_state(
+            11,
+            11,
+            vec![snake("us", &[(2, 5), (1, 5), (0, 5)]), snake("them", &[(8, 5), (9, 5), (10, 5)])],
+            &[(2, 7), (5, 5)],
+        );
+        let target = |config: &Config| {
+            evaluate_food(&state.you.body[0], &state.board, &state.you, &state.game.ruleset, config).map(|(_, dir, food)| (dir, food))
+        };
+
+        // The nearer food wins on its own...
+        assert_eq!(target(&Config::default()), Some((Direction::Up, Coord { x: 2, y: 7 })));
+        // ...but in a duel the center food is worth the extra move for the control it gives
+        let dueling = Config { duel_food_control: true, ..Config::default() };
+        assert_eq!(target(&dueling), Some((Direction::Right, Coord { x: 5, y: 5 })));
+
+        // With a third snake on the board it is no longer a duel
+        let mut crowded = state.clone();
+        crowded.board.snakes.push(snake("other", &[(5, 10), (4, 10), (3, 10)]));
+        assert_eq!(duel_rival(&crowded.board, &crowded.you, &dueling), None);
+    }
+
+    #[test]
+    fn wrapped_games_measure_food_and_opponents_across_the_edges() {
+        let you = snake("us", &[(0, 5), (1, 5), (2, 5)]);
+        let near = snake("near", &[(10, 8), (9, 8), (8, 8)]);
+        let far = snake("far", &[(5, 9), (5, 8), (5, 7)]);
+        let mut state = game_state(11, 11, vec![you.clone(), far, near], &[(10, 5)]);
+        state.game.ruleset.name = "wrapped".to_string();
+        assert_eq!(wrapped_distance(&Coord { x: 0, y: 5 }, &Coord { x: 10, y: 5 }, &state.board), 1);
+
+        let config = Config::default().for_ruleset(&state.game.ruleset).expect("wrapped config");
+        let (_, dir, food) = evaluate_food(&you.body[0], &state.board, &you, &state.game.ruleset, &config).expect("food");
+        assert_eq!((dir, food.clone()), (Direction::Left, Coord { x: 10, y: 5 }));
+        assert_eq!(a_star(&state.board, &you.body[0], &food, config.toroidal, |_| 1).map(|path| path.len()), Some(1));
+
+        state.you.health = 3;
+        state.board.snakes[0].health = 3;
+        assert_eq!(hunger(&you.body[0], &state.you, &state.board, &state.game.ruleset, &config), Hunger::Urgent);
+
+        let order: Vec<&str> = nearest_opponents(&state.board, &you, 2, &config).iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(order, ["near", "far"]);
+    }
+
+    #[test]
+    fn malformed_environment_overrides_are_reported() {
+        let vars = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+        };
+        let load = |vars: HashMap<String, String>| Config::load_from("snake.toml", None, &move |key| vars.get(key).cloned());
+
+        let err = load(vars(&[("STRICT_CONFIG", "maybe")])).expect_err("a malformed STRICT_CONFIG is an error");
+        assert!(err.contains("STRICT_CONFIG"), "{err}");
+
+        let overrides = [("WEIGHT_SPACE", "lots"), ("MAXIMIN", "perhaps")];
+        let err = load(vars(&[("STRICT_CONFIG", "1"), overrides[0], overrides[1]])).expect_err("strict rejects the overrides");
+        assert!(err.contains("WEIGHT_SPACE") && err.contains("MAXIMIN"), "{err}");
+        let lenient = load(vars(&overrides)).expect("lenient keeps going");
+        assert_eq!(lenient.weights.space, Config::default().weights.space);
+        assert!(!lenient.maximin);
+
+        // Well-formed overrides still apply
+        let applied = load(vars(&[("STRICT_CONFIG", "1"), ("WEIGHT_SPACE", "7.5")])).expect("valid overrides");
+        assert_eq!(applied.weights.space, 7.5);
+    }
+
+    #[test]
+    fn ties_go_to_the_region_holding_our_tail() {
+        // Our body and a stub split the board into two 5x5 rooms; the tail
+        // curls into one of them
+        let config = Config { weights: Weights { center: 0.0, ..Weights::default() }, ..fixed_depth(1) };
+        let memory = GameMemory::default();
+        for (tail, toward) in [((4, 4), Direction::Left), ((6, 4), Direction::Right)] {
+            let us = snake("us", &[(5, 2), (5, 3), (5, 4), tail]);
+            let state = game_state(11, 5, vec![us.clone(), snake("stub", &[(5, 0), (5, 1), (5, 1)])], &[]);
+            let blocked = blocked_cells(&state.board, config.model_opponent_growth);
+            let room = |dir| flood_region(&state.board, &get_new_position(&us.body[0], dir), &blocked, 100).len();
+            assert_eq!(room(Direction::Left), room(Direction::Right));
+
+            // Both rooms score the same, up to the rounding noise of a search blend
+            let terms = PositionTerms::new(&state, &config, &memory);
+            let mut moves = evaluate_moves(Direction::all().into_iter().map(Move::new).collect(), &state, &terms);
+            let score = |moves: &[Move], dir| moves.iter().find(|m| m.direction == dir).map(|m| m.score).unwrap();
+            assert_eq!(score(&moves, Direction::Left), score(&moves, Direction::Right));
+            moves.iter_mut().filter(|m| m.direction == Direction::Right).for_each(|m| m.score += TIE_EPSILON / 2.0);
+            assert_eq!(keep_tail_in_reach(&moves, &state.board, &us, &config), Some(toward));
+        }
+    }
+
+    // The shared food term for the position after our move in `dir`
+    fn food_after(state: &GameState, config: &Config, dir: Direction) -> f64 {
+        let memory = GameMemory::default();
+        let terms = PositionTerms::new(state, config, &memory);
+        let (after, moved) = step_onto(&get_new_position(&state.you.body[0], dir), &state.you, &state.board);
+        food_term(&after, &moved, &terms)
+    }
+
+    #[test]
+    fn food_toward_a_larger_head_is_declined() {
+        let state = game_state(
+            11,
+            11,
+            vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), snake("them", &[(9, 5), (9, 6), (9, 7), (9, 8), (9, 9)])],
+            &[(7, 5)],
+        );
+        assert!(food_after(&state, &Config::default(), Direction::Right) > 0.0);
+        let wary = Config { food_safe_distance: Some(4), ..Config::default() };
+        assert_eq!(food_after(&state, &wary, Direction::Right), 0.0);
+    }
+
+    #[test]
+    fn duel_food_that_gives_up_space_is_declined() {
+        let state = game_state(11, 11, vec![snake("us", &[(4, 5), (4, 4), (4, 3)]), snake("them", &[(6, 6), (6, 7), (6, 8)])], &[(1, 5)]);
+        assert!(food_after(&state, &Config::default(), Direction::Left) > 0.0);
+        let dueling = Config { duel_food_min_health: Some(20), ..Config::default() };
+        assert_eq!(food_after(&state, &dueling, Direction::Left), 0.0);
+    }
+
+    #[test]
+    fn food_in_a_pocket_too_small_to_grow_into_is_declined() {
+        let state = game_state(
+            7,
+            7,
+            vec![snake("us", &[(2, 0), (3, 0), (4, 0)]), snake("them", &[(0, 1), (1, 1), (2, 1), (2, 2), (2, 3)])],
+            &[(0, 0)],
+        );
+        let config = Config::default();
+        let memory = GameMemory::default();
+        assert!(PositionTerms::new(&state, &config, &memory).food.is_some_and(|plan| plan.score > 0.0));
+        assert_eq!(food_after(&state, &config, Direction::Left), 0.0);
+    }
+
+    #[test]
+    fn food_desirability_picks_what_the_leaves_chase() {
+        let state = game_state(
+            11,
+            11,
+            vec![snake("us", &[(2, 5), (1, 5), (0, 5)]), snake("them", &[(8, 5), (9, 5), (10, 5)])],
+            &[(2, 7), (5, 5)],
+        );
+        let memory = GameMemory::default();
+        let target = |config: &Config| PositionTerms::new(&state, config, &memory).food.map(|plan| plan.target);
+
+        // The nearer food wins on its own...
+        let plain = Config::default();
+        assert_eq!(target(&plain), Some(Coord { x: 2, y: 7 }));
+        assert!(food_after(&state, &plain, Direction::Up) > 0.0);
+        assert_eq!(food_after(&state, &plain, Direction::Right), 0.0);
+
+        // ...but in a duel the center food is worth the extra move for the control it gives
+        let dueling = Config { duel_food_control: true, ..Config::default() };
+        assert_eq!(target(&dueling), Some(Coord { x: 5, y: 5 }));
+        assert!(food_after(&state, &dueling, Direction::Right) > 0.0);
+        assert_eq!(food_after(&state, &dueling, Direction::Up), 0.0);
+    }
+
+    #[test]
+    fn self_test_passes_and_reports_mismatches() {
+        assert_eq!(self_test(&Config::default()), Ok(()));
+        assert_eq!(self_test(&fixed_depth(1)), Ok(()));
+
+        // A flood fill capped below the fixture's region is caught with a diagnostic
+        let capped = Config { flood_fill_max_cells: Some(5), ..Config::default() };
+        let error = self_test(&capped).unwrap_err();
+        assert!(error.starts_with("reachable_space:"), "{error}");
+    }
+
+    #[test]
+    fn head_to_head_threat_scales_with_escape_room() {
+        let scaled = Config { escape_scaled_threat: true, ..Config::default() };
+        let plain = Config::default();
+
+        // Two cells from a longer snake's head in the open, three ways on
+        let us = snake("us", &[(5, 5), (5, 4), (5, 3)]);
+        let open = game_state(11, 11, vec![us.clone(), snake("them", &[(7, 6), (8, 6), (9, 6), (10, 6)])], &[]);
+        let pos = Coord { x: 5, y: 6 };
+        assert_eq!(evaluate_threats(&pos, &open.board, &us, &plain), -150.0);
+        assert_eq!(evaluate_threats(&pos, &open.board, &us, &scaled), -75.0);
+
+        // The same distance in the corner, with a single way on
+        let us = snake("us", &[(0, 1), (1, 1), (2, 1), (3, 1)]);
+        let cramped = game_state(11, 11, vec![us.clone(), snake("them", &[(2, 0), (3, 0), (4, 0), (5, 0), (6, 0)])], &[]);
+        let pos = Coord { x: 0, y: 0 };
+        assert_eq!(evaluate_threats(&pos, &cramped.board, &us, &plain), -150.0);
+        assert_eq!(evaluate_threats(&pos, &cramped.board, &us, &scaled), -225.0);
+    }
+
+    #[actix_web::test]
+    async fn opponent_predictions_are_scored_next_turn() {
+        let config = Config { track_predictions: true, ..fixed_depth(1) };
+        let app_state = web::Data::new(AppState::default());
+        let app = actix_web::test::init_service(
+            App::new()
+                .app_data(web::Data::new(RwLock::new(config.weights.clone())))
+                .app_data(web::Data::new(config.clone()))
+                .app_data(app_state.clone())
+                .route("/move", web::post().to(r#move))
+                .route("/end", web::post().to(end)),
+        )
+        .await;
+        let mut state = game_state(11, 11, vec![snake("us", &[(2, 2), (2, 1), (2, 0)]), snake("them", &[(8, 8), (8, 7), (8, 6)])], &[]);
+        // Plays a turn, then moves the board on with the opponent going `theirs(board)`
+        let play = |state: &GameState, theirs: fn(&Board, &Config) -> Direction| {
+            let state_now = state.clone();
+            let app = &app;
+            let config = &config;
+            async move {
+                let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state_now).to_request();
+                let response: serde_json::Value = actix_web::test::call_and_read_body_json(app, request).await;
+                let ours = Direction::all().into_iter().find(|dir| response["move"] == dir.as_str()).expect("a move");
+                let mut next = state_now.clone();
+                next.board = simulate_turn(&state_now.board, &[("us", ours), ("them", theirs(&state_now.board, config))]);
+                next.you = next.board.snakes[0].clone();
+                next.turn += 1;
+                next
+            }
+        };
+
+        // They take the move our model expects, then one it doesn't
+        state = play(&state, |board, config| greedy_reply(board, "them", config)).await;
+        state = play(&state, |board, config| {
+            let expected = greedy_reply(board, "them", config);
+            let them = &board.snakes[1];
+            safe_moves(them, board).into_iter().find(|dir| *dir != expected).unwrap()
+        })
+        .await;
+        let request = actix_web::test::TestRequest::post().uri("/move").set_json(&state).to_request();
+        assert!(actix_web::test::call_service(&app, request).await.status().is_success());
+
+        let metrics = app_state.metrics.lock().unwrap().clone();
+        assert_eq!((metrics.predictions, metrics.prediction_hits), (2, 1));
+        let request = actix_web::test::TestRequest::post().uri("/end").set_json(&state).to_request();
+        assert!(actix_web::test::call_service(&app, request).await.status().is_success());
+        assert!(app_state.games.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_steers_clear_of_hazard_cells() {
+        let mut state = game_state(11, 11, vec![snake("us", &[(5, 5), (5, 4), (5, 3)]), snake("them", &[(1, 9), (1, 8), (1, 7)])], &[]);
+        state.game.ruleset.name = "royale".to_string();
+        state.board.hazards = coords(&[(4, 5)]);
+        let mut config = fixed_depth(3);
+        config.weights.hazard_cell = 100_000.0;
+        let decision = bilinear_duel(&state, &config, &GameMemory::default(), no_deadline());
+        let score = |dir| decision.scores.iter().find(|m| m.direction == dir).unwrap().score;
+        assert!(score(Direction::Left) < score(Direction::Up) - 50_000.0);
+        assert!(score(Direction::Left) < score(Direction::Right) - 50_000.0);
+    }
+
+    #[test]
+    fn components_split_by_a_wall() {
+        // Their body runs down column 5 with the head turned off to the right
+        let them = snake("them", &[(6, 0), (5, 0), (5, 1), (5, 2), (5, 3), (5, 4), (5, 4)]);
+        let state = game_state(11, 5, vec![snake("us", &[(2, 2), (1, 2), (0, 2)]), them], &[]);
+        let components = board_components(&state.board);
+        let mut sizes = components.sizes.clone();
+        sizes.sort();
+        // Our moving tail frees a cell on the left; their head takes one on the right
+        assert_eq!(sizes, vec![23, 24]);
+        assert_eq!((components.space_for("us"), components.space_for("them")), (23, 24));
+        assert!(components.separated("us") && components.separated("them"));
+
+        let config = Config { separated_endgame: true, ..fixed_depth(1) };
+        let decision = explain_decision(&state, &config, &GameMemory::default());
+        assert_eq!(decision.reason, "separated endgame");
+
+        // Stopping short of the top edge, the wall leaves one board again
+        let mut open = state.clone();
+        open.board.snakes[1] = snake("them", &[(6, 0), (5, 0), (5, 1), (5, 2), (5, 3)]);
+        let components = board_components(&open.board);
+        assert_eq!(components.sizes.len(), 1);
+        assert!(!components.separated("us"));
+        assert_ne!(explain_decision(&open, &config, &GameMemory::default()).reason, "separated endgame");
+    }
+
+    #[test]
+    fn duel_openings_head_for_the_center() {
+        // Standard spawns with the center food and a pellet on the edge right next to us
+        let us = snake("us", &[(1, 1), (1, 1), (1, 1)]);
+        let them = snake("them", &[(9, 9), (9, 9), (9, 9)]);
+        let mut state = game_state(11, 11, vec![us, them], &[(5, 5), (0, 1)]);
+        state.turn = 0;
+        let config = Config { duel_opening_turns: Some(5), ..fixed_depth(2) };
+        let memory = GameMemory::default();
+
+        let decision = explain_decision(&state, &config, &memory);
+        assert_eq!(decision.reason, "duel opening");
+        assert!([Direction::Up, Direction::Right].contains(&decision.direction), "{}", decision.direction);
+        // Left to the generic scoring, the edge pellet wins
+        let generic = explain_decision(&state, &fixed_depth(2), &memory);
+        assert!([Direction::Down, Direction::Left].contains(&generic.direction), "{}", generic.direction);
+
+        // Past the opening turns, or with a third snake, the heuristic stays out of it
+        let later = GameState { turn: 5, ..state.clone() };
+        assert_ne!(explain_decision(&later, &config, &memory).reason, "duel opening");
+        let mut crowded = state.clone();
+        crowded.board.snakes.push(snake("third", &[(9, 1), (9, 1), (9, 1)]));
+        assert_ne!(explain_decision(&crowded, &config, &memory).reason, "duel opening");
+    }
+
+    #[test]
+    fn the_space_floor_rules_out_cramped_moves() {
+        // Left runs into the four cells of column 0, with food as bait; right into the open
+        let state = game_state(
+            7,
+            7,
+            vec![snake("us", &[(1, 3), (1, 2), (1, 1), (1, 0), (2, 0), (3, 0)]), snake("wall", &[(2, 5), (2, 4), (1, 4), (0, 4), (0, 5)])],
+            &[(0, 3)],
+        );
+        let memory = GameMemory::default();
+        let floored = Config { space_floor: Some(10), ..fixed_depth(2) };
+        let decision = explain_decision(&state, &floored, &memory);
+        assert_eq!((decision.direction, decision.reason), (Direction::Right, "space floor"));
+
+        // A floor no move can keep falls back to the roomiest move
+        let unreachable = Config { space_floor: Some(100), ..fixed_depth(2) };
+        let decision = explain_decision(&state, &unreachable, &memory);
+        assert_eq!((decision.direction, decision.reason), (Direction::Right, "below space floor"));
+    }
+
+    #[test]
+    fn pressure_squeezes_the_dangerous_opponent() {
+        let you = snake("us", &[(2, 1), (2, 0), (3, 0), (4, 0)]);
+        let big = snake("big", &[(0, 1), (0, 2), (0, 3), (0, 4), (0, 5), (0, 6)]);
+        let small = snake("small", &[(8, 8), (8, 9), (8, 10)]);
+        let state = game_state(11, 11, vec![you.clone(), small, big], &[]);
+        let config = Config { personality: Personality::Aggressive, ..Config::default() };
+        let target = dangerous_opponent(&state.board, &you, &config).map(|snake| snake.id.as_str());
+        assert_eq!(target, Some("big"));
+
+        let scored = |config: &Config| {
+            let mut moves: Vec<Move> = [Direction::Left, Direction::Up, Direction::Right]
+                .into_iter()
+                .map(Move::new)
+                .collect();
+            reward_pressure(&mut moves, &state.board, &you, config);
+            moves.iter().map(|m| m.score).collect::<Vec<_>>()
+        };
+        // Only stepping in front of big's head shuts it into the corner
+        let scores = scored(&config);
+        assert!(scores[0] > 0.0 && scores[0] > scores[1] && scores[0] > scores[2], "{scores:?}");
+        assert_eq!(scored(&Config::default()), [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn a_missing_turn_is_the_first_turn() {
+        let mut payload = serde_json::to_value(game_state(11, 11, vec![snake("us", &[(1, 1), (1, 1), (1, 1)]), snake("them", &[(9, 9), (9, 9), (9, 9)])], &[(5, 5)])).unwrap();
+        payload.as_object_mut().unwrap().remove("turn");
+        let state: GameState = serde_json::from_value(payload).expect("turn is optional");
+        assert_eq!(state.turn, 0);
+
+        // Every turn-dependent feature sees turn 0, exactly as if it had been sent
+        let config = Config {
+            personality: Personality::Aggressive,
+            aggression_start_turn: 20,
+            duel_opening_turns: Some(3),
+            ..fixed_depth(2)
+        };
+        assert_eq!(config.center_weight(state.turn), config.weights.center);
+        assert_eq!(config.for_turn(state.turn).map(|c| c.personality), Some(Personality::Balanced));
+        let memory = GameMemory::default();
+        let decision = explain_decision(&state, &config, &memory);
+        assert_eq!(decision.reason, "duel opening");
+        let negative = GameState { turn: -7, ..state.clone() };
+        assert_eq!(explain_decision(&negative, &config, &memory).direction, decision.direction);
+    }
+
+    #[test]
+    fn hungry_search_follows_the_planned_route() {
+        let mut you = snake("us", &[(5, 5), (5, 4), (5, 3)]);
+        you.health = 5;
+        let state = game_state(11, 11, vec![you, snake("them", &[(10, 0), (10, 1), (10, 2)])], &[(3, 5), (7, 5), (8, 5), (9, 5)]);
+        let config = Config { plan_food_route: true, ..fixed_depth(2) };
+        let memory = GameMemory::default();
+        let terms = PositionTerms::new(&state, &config, &memory);
+        assert_eq!(terms.hunger, Hunger::Urgent);
+        let target = route_target(&state.board, &state.you, &state.game.ruleset, &config);
+        assert_eq!(terms.food.as_ref().map(|plan| plan.target.clone()), target);
+
+        let decision = bilinear_duel(&state, &config, &memory, no_deadline());
+        let route = plan_food_route(&state.board, &state.you, &state.game.ruleset, &config);
+        assert_eq!(Some(decision.direction), route.first().copied());
+    }
+
+    #[test]
+    fn desperate_bonus_reaches_the_search() {
+        // The only food lies beyond a hazard band that costs more health than we have
+        let mut you = snake("us", &[(5, 5), (5, 4), (5, 3)]);
+        you.health = 10;
+        let mut state = game_state(11, 11, vec![you, snake("them", &[(0, 10), (1, 10), (2, 10)])], &[(5, 8)]);
+        state.game.ruleset.name = "royale".to_string();
+        state.game.ruleset.settings.hazard_damage_per_turn = 14;
+        state.board.hazards = (0..11).map(|x| Coord { x, y: 7 }).collect();
+        // Deeper lines can close in on the food from any side, so look one move ahead
+        let config = fixed_depth(1);
+        let memory = GameMemory::default();
+        assert_eq!(PositionTerms::new(&state, &config, &memory).hunger, Hunger::Desperate);
+
+        let decision = bilinear_duel(&state, &config, &memory, no_deadline());
+        let score = |dir| decision.scores.iter().find(|m| m.direction == dir).unwrap().score;
+        assert_eq!(decision.direction, Direction::Up);
+        assert!(score(Direction::Up) > score(Direction::Left) + 500.0);
+        assert!(score(Direction::Up) > score(Direction::Right) + 500.0);
+    }
+
+    #[test]
+    fn zero_health_opponents_are_ignored() {
+        // The only way out of the corner runs over a snake that just starved
+        let dead = Snake { health: 0, ..snake("dead", &[(1, 0), (2, 0), (3, 0), (4, 0)]) };
+        let state = game_state(7, 7, vec![snake("us", &[(0, 0), (0, 1), (0, 2)]), dead], &[]);
+        let memory = GameMemory::default();
+
+        let decision = explain_decision(&state, &fixed_depth(2), &memory);
+        assert_eq!((decision.direction, decision.reason), (Direction::Right, "only safe move"));
+
+        // Counted as a live body it would leave us no safe move at all
+        assert!(safe_moves(&state.you, &state.board).is_empty());
+        let counted = Config { ignore_dead_snakes: false, ..fixed_depth(2) };
+        let decision = explain_decision(&state, &counted, &memory);
+        assert_ne!(decision.reason, "only safe move");
+    }
+
+    #[test]
+    fn corner_trap_cuts_off_a_wall_hugger() {
+        // A shorter snake runs right along the bottom wall; we come down from the open side
+        let them = snake("them", &[(5, 0), (4, 0), (3, 0)]);
+        let state = game_state(11, 11, vec![snake("us", &[(6, 1), (6, 2), (6, 3), (6, 4)]), them], &[]);
+        let rewards = |config: &Config| {
+            let mut moves: Vec<Move> = [Direction::Down, Direction::Left, Direction::Right].into_iter().map(Move::new).collect();
+            reward_corner_trap(&mut moves, &state.board, &state.you, config);
+            moves.iter().map(|m| m.score).collect::<Vec<_>>()
+        };
+
+        let aggressive = Config { personality: Personality::Aggressive, ..Config::default() };
+        let trap = aggressive.weights.corner_trap;
+        // Down takes its way forward, which counts double; left takes its way up
+        assert_eq!(rewards(&aggressive), vec![2.0 * trap, trap, 0.0]);
+        let after = simulate_turn(&state.board, &[("us", Direction::Down)]);
+        assert_eq!(safe_moves(&after.snakes[1], &after), vec![Direction::Up]);
+
+        assert_eq!(rewards(&Config::default()), vec![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn solo_snake_follows_the_fill_cycle() {
+        let cycle = fill_cycle(6, 6).expect("an even board has a cycle");
+        assert_eq!(cycle.len(), 36);
+        let at = |i: usize| cycle[i % cycle.len()].clone();
+        let body: Vec<(i32, i32)> = [2, 1, 0].into_iter().map(|i| (at(i).x, at(i).y)).collect();
+        let food = at(10);
+        let mut state = game_state(6, 6, vec![snake("us", &body)], &[(food.x, food.y)]);
+        state.game.ruleset.name = "solo".to_string();
+        let config = Config { fill_cycle: true, ..fixed_depth(1) };
+        let memory = GameMemory::default();
+
+        // Twice around the board, eating once on the way
+        let mut cycled = 0;
+        for turn in 2..74 {
+            let decision = explain_decision(&state, &config, &memory);
+            // Along the walls the cycle's step is often the only safe move anyway
+            assert!(["fill cycle", "only safe move"].contains(&decision.reason), "turn {turn}: {}", decision.reason);
+            cycled += (decision.reason == "fill cycle") as usize;
+            assert!(safe_moves(&state.you, &state.board).contains(&decision.direction));
+            state.board = simulate_turn(&state.board, &[("us", decision.direction)]);
+            state.you = state.board.snakes.first().expect("still alive").clone();
+            assert_eq!(state.you.body[0], at(turn + 1));
+        }
+        assert_eq!(state.you.body.len(), 4);
+        assert!(cycled > 0);
+    }
+
+    #[test]
+    fn circling_without_growing_turns_on_decisive_weights() {
+        let config = Config { stalemate_turns: Some(4), ..Config::default() };
+        let mut memory = GameMemory::default();
+        // Round and round a 2x2 square, never eating
+        let square = [(1, 1), (1, 2), (2, 2), (2, 1)];
+        for turn in 0..8 {
+            let cells: Vec<(i32, i32)> = (0..3).map(|k| square[(turn + 4 - k) % 4]).collect();
+            memory.record_turn(&snake("us", &cells), 20, None);
+        }
+        assert!(memory.stalled_turns >= 4);
+        let decisive = config.for_stalemate(&memory).expect("stalemate weights");
+        assert_eq!(decisive.weights.food, config.weights.food * config.stalemate_shift);
+
+        // Growing counts as progress
+        let mut grown = snake("us", &[(1, 1), (1, 2), (2, 2), (2, 1)]);
+        grown.body.rotate_left(1);
+        memory.record_turn(&grown, 20, None);
+        assert!(config.for_stalemate(&memory).is_none());
+    }
 }